@@ -0,0 +1,32 @@
+//! Archiving completed items automatically once they have been done for a
+//! while. `todo archive` and `todo unarchive` work on the in-memory lists
+//! instead, through [`TodoList`](crate::todo::TodoList).
+
+use chrono::{Duration, Local};
+use rusqlite::{params, Connection, Result};
+
+/// Archives items completed more than `days` days ago, recording an
+/// `archive` event for each. Returns how many were archived.
+pub fn archive_completed(conn: &Connection, days: u32) -> Result<usize> {
+    let now = Local::now();
+    let cutoff = (now - Duration::days(days.into()))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO todo_events (uid, owner, list_name, item_number, actor, at, kind)
+        SELECT t.uid, u.user_name, t.list_name, t.item_number, 'auto-archive', ?2, 'archive'
+        FROM todos t JOIN users u ON u.id = t.user_id
+        WHERE t.is_completed = 1 AND t.completed_at < ?1 AND t.archived_at IS NULL
+            AND t.trash_id IS NULL",
+        params![cutoff, now.format("%Y-%m-%d %H:%M:%S").to_string()],
+    )?;
+    let archived = tx.execute(
+        "UPDATE todos SET archived_at = ?2
+        WHERE is_completed = 1 AND completed_at < ?1 AND archived_at IS NULL
+            AND trash_id IS NULL",
+        params![cutoff, now.format("%Y-%m-%d %H:%M:%S").to_string()],
+    )?;
+    tx.commit()?;
+    Ok(archived)
+}
@@ -0,0 +1,142 @@
+//! Timestamped snapshots of the database, taken with SQLite's online backup
+//! API so they are consistent even while another process is writing.
+
+use crate::db::{migrate, SCHEMA_VERSION};
+use chrono::{Datelike, Local, NaiveDateTime};
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PREFIX: &str = "todo-";
+const SUFFIX: &str = ".db";
+const STAMP: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// Snapshots go to `dir`, or a `backups` directory next to the database.
+pub fn snapshot_dir(conn: &Connection, dir: Option<&Path>) -> Result<PathBuf, String> {
+    if let Some(dir) = dir {
+        return Ok(dir.to_path_buf());
+    }
+    match conn.path() {
+        Some(path) if !path.is_empty() => Ok(Path::new(path)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("backups")),
+        _ => Err("The database has no file to back up.".to_string()),
+    }
+}
+
+fn snapshot_time(path: &Path) -> Option<NaiveDateTime> {
+    let name = path.file_name()?.to_str()?;
+    let stamp = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+    NaiveDateTime::parse_from_str(stamp, STAMP).ok()
+}
+
+/// Snapshots in `dir`, newest first.
+pub fn list(dir: &Path) -> Result<Vec<(NaiveDateTime, PathBuf)>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+    };
+    let mut snapshots: Vec<(NaiveDateTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((snapshot_time(&entry.path())?, entry.path())))
+        .collect();
+    snapshots.sort_by_key(|(time, _)| Reverse(*time));
+    Ok(snapshots)
+}
+
+/// Copies the live database to a new snapshot in `dir`.
+pub fn create(conn: &Connection, dir: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let name = format!("{}{}{}", PREFIX, Local::now().format(STAMP), SUFFIX);
+    let path = dir.join(name);
+    // Write under another name first so a failed backup never looks like a snapshot.
+    let partial = path.with_extension("partial");
+    let result = conn
+        .backup(DatabaseName::Main, &partial, None)
+        .map_err(|e| e.to_string())
+        .and_then(|_| fs::rename(&partial, &path).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result.map(|_| path)
+}
+
+/// Which snapshots to keep, given their times newest first: the newest of
+/// each of the last `daily` days and of each of the last `weekly` ISO weeks
+/// that have one. The newest snapshot is always kept, whatever the policy.
+pub fn retained(times: &[NaiveDateTime], daily: usize, weekly: usize) -> Vec<bool> {
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    times
+        .iter()
+        .enumerate()
+        .map(|(index, time)| {
+            let day = time.date();
+            let week = (day.iso_week().year(), day.iso_week().week());
+            let keep_day = days.len() < daily && days.insert(day);
+            let keep_week = weeks.len() < weekly && weeks.insert(week);
+            index == 0 || keep_day || keep_week
+        })
+        .collect()
+}
+
+/// Deletes the snapshots that fall outside the retention policy.
+pub fn rotate(dir: &Path, daily: usize, weekly: usize) -> Result<Vec<PathBuf>, String> {
+    let snapshots = list(dir)?;
+    let times: Vec<NaiveDateTime> = snapshots.iter().map(|(time, _)| *time).collect();
+    let mut removed = Vec::new();
+    for ((_, path), keep) in snapshots.into_iter().zip(retained(&times, daily, weekly)) {
+        if !keep {
+            fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// Finds a snapshot by path, or by file name within `dir`.
+pub fn resolve(dir: &Path, snapshot: &Path) -> PathBuf {
+    if snapshot.exists() {
+        snapshot.to_path_buf()
+    } else {
+        dir.join(snapshot)
+    }
+}
+
+/// Fails unless `snapshot` is an intact database this build can migrate.
+pub fn verify(snapshot: &Path) -> Result<(), String> {
+    let source = Connection::open_with_flags(snapshot, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("{}: {}", snapshot.display(), e))?;
+    let check = || -> rusqlite::Result<(String, usize)> {
+        let status = source.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        let version = source.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        Ok((status, version))
+    };
+    match check() {
+        Ok((status, _)) if status != "ok" => Err(format!(
+            "{} failed the integrity check: {}",
+            snapshot.display(),
+            status
+        )),
+        Ok((_, version)) if version > SCHEMA_VERSION => Err(format!(
+            "{} is from schema version {}, but this build only supports up to {}",
+            snapshot.display(),
+            version,
+            SCHEMA_VERSION
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{}: {}", snapshot.display(), e)),
+    }
+}
+
+/// Verifies `snapshot`, then copies it over the live database and migrates it.
+pub fn restore(conn: &mut Connection, snapshot: &Path) -> Result<(), String> {
+    verify(snapshot)?;
+    conn.restore(DatabaseName::Main, snapshot, None::<fn(_)>)
+        .and_then(|_| migrate(conn))
+        .map_err(|e| e.to_string())
+}
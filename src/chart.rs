@@ -0,0 +1,175 @@
+//! Charts drawn with Unicode block characters for `todo chart`: a
+//! contribution-style heatmap of completions and a per-list burndown of open
+//! versus done items. Like `todo stats`, they are worked out from when items
+//! were created and completed, leaving out the trash.
+
+use crate::todo::{TodoList, User};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+/// Heatmap cells from no completions to the busiest day.
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+/// Partial blocks for the top of a bar, in eighths.
+const EIGHTHS: [char; 8] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇'];
+
+fn date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// The day of each completion across the user's lists.
+pub fn completion_dates(user: &User) -> Vec<NaiveDate> {
+    user.todo_lists
+        .values()
+        .flat_map(|list| list.todos.iter())
+        .filter(|todo| !todo.is_deleted && todo.is_completed)
+        .filter_map(|todo| date(todo.completed_at.as_deref()?))
+        .collect()
+}
+
+/// When each item of a list was created, and completed if it is done.
+pub fn list_history(list: &TodoList) -> Vec<(NaiveDate, Option<NaiveDate>)> {
+    list.todos
+        .iter()
+        .filter(|todo| !todo.is_deleted)
+        .filter_map(|todo| {
+            let completed = match (todo.is_completed, &todo.completed_at) {
+                (true, Some(at)) => date(at),
+                _ => None,
+            };
+            Some((date(&todo.datetime)?, completed))
+        })
+        .collect()
+}
+
+/// Completions per day for the 53 weeks up to `today`, one column per week
+/// and one row per weekday, with month names above.
+pub fn heatmap(dates: &[NaiveDate], today: NaiveDate) -> Vec<String> {
+    let this_week = today - Duration::days(today.weekday().num_days_from_monday().into());
+    let first = this_week - Duration::weeks(52);
+    let mut counts: HashMap<NaiveDate, usize> = HashMap::new();
+    for &day in dates.iter().filter(|&&day| day >= first && day <= today) {
+        *counts.entry(day).or_default() += 1;
+    }
+    let busiest = counts.values().copied().max().unwrap_or(0);
+
+    let weeks: Vec<NaiveDate> = (0..53).map(|week| first + Duration::weeks(week)).collect();
+    let mut months = String::from("    ");
+    let mut skip = 0;
+    for (index, week) in weeks.iter().enumerate() {
+        if skip > 0 {
+            skip -= 1;
+        } else if (index == 0 && weeks[3].month() == week.month())
+            || (index > 0 && week.month() != weeks[index - 1].month())
+        {
+            let name = week.format("%b").to_string();
+            skip = name.chars().count() - 1;
+            months.push_str(&name);
+        } else {
+            months.push(' ');
+        }
+    }
+    let mut lines = vec![months.trim_end().to_string()];
+    for weekday in 0..7 {
+        let label = match weekday {
+            0 => "Mon ",
+            2 => "Wed ",
+            4 => "Fri ",
+            _ => "    ",
+        };
+        let mut line = String::from(label);
+        for week in &weeks {
+            let day = *week + Duration::days(weekday);
+            if day > today {
+                break;
+            }
+            let count = counts.get(&day).copied().unwrap_or(0);
+            // Scale non-zero counts onto the four darker shades.
+            let shade = if count == 0 {
+                0
+            } else {
+                (count * 4).div_ceil(busiest)
+            };
+            line.push(SHADES[shade]);
+        }
+        lines.push(line);
+    }
+    let total: usize = counts.values().sum();
+    lines.push(format!(
+        "    {} completions in the last year    Less {} More",
+        total,
+        SHADES
+            .iter()
+            .map(char::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+    lines
+}
+
+/// Open and done items at the end of each day from `from` to `to`, as a
+/// stacked bar per day `height` rows tall: done at the bottom, open above.
+pub fn burndown(
+    items: &[(NaiveDate, Option<NaiveDate>)],
+    from: NaiveDate,
+    to: NaiveDate,
+    height: usize,
+) -> Vec<String> {
+    let days: Vec<NaiveDate> = from.iter_days().take_while(|day| *day <= to).collect();
+    let counts: Vec<(usize, usize)> = days
+        .iter()
+        .map(|day| {
+            let existing = items.iter().filter(|(created, _)| created <= day);
+            let done = existing
+                .clone()
+                .filter(|(_, completed)| completed.is_some_and(|at| at <= *day))
+                .count();
+            (existing.count() - done, done)
+        })
+        .collect();
+    let most = counts
+        .iter()
+        .map(|(open, done)| open + done)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let width = most.to_string().len();
+    let eighths = |count: usize| (count * height * 8 + most / 2) / most;
+
+    let mut lines = Vec::new();
+    for row in (0..height).rev() {
+        let label = match row {
+            _ if row + 1 == height => most.to_string(),
+            0 => "0".to_string(),
+            _ => String::new(),
+        };
+        let mut line = format!("{:>width$} │", label);
+        for &(open, done) in &counts {
+            let total = eighths(open + done).saturating_sub(row * 8).min(8);
+            let done = eighths(done).saturating_sub(row * 8).min(8);
+            line.push(match (done, total) {
+                (8, _) => '█',
+                (0, 0) => ' ',
+                (0, _) => '░',
+                (partial, _) => EIGHTHS[partial],
+            });
+        }
+        lines.push(line);
+    }
+    lines.push(format!("{:>width$} └{}", "", "─".repeat(days.len())));
+    let first = from.format("%Y-%m-%d").to_string();
+    let last = to.format("%Y-%m-%d").to_string();
+    let gap = days.len().saturating_sub(first.len() + last.len()).max(1);
+    lines.push(format!(
+        "{:>width$}  {}{}{}",
+        "",
+        first,
+        " ".repeat(gap),
+        last
+    ));
+    let (open, done) = counts.last().copied().unwrap_or((0, 0));
+    lines.push(format!(
+        "{:>width$}  █ done ({})  ░ open ({})",
+        "", done, open
+    ));
+    lines
+}
@@ -0,0 +1,109 @@
+//! GitHub-style Markdown checklists.
+//!
+//! Each heading starts a list and each `- [ ]` / `- [x]` line is an item.
+//! Nested checkboxes become items of the same list, since items can't have
+//! sub-items; other indented lines under an item become its notes. Notes
+//! that start with `[` are written as `\[`, so they don't read back as items.
+//! Checked items count as completed at the time of the import.
+
+use crate::exchange::ImportReport;
+use crate::todo::{get_current_date_time, TodoItem, User};
+
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    if text.len() == line.len() || !text.starts_with(' ') {
+        return None;
+    }
+    Some(text.trim())
+}
+
+/// Parses `- [ ] title`, `* [x] title` or `+ [X] title`; `None` when the
+/// line is not a checkbox at all.
+fn checkbox(line: &str) -> Option<Result<(bool, &str), String>> {
+    let rest = line.trim_start();
+    let rest = rest
+        .strip_prefix("- ")
+        .or_else(|| rest.strip_prefix("* "))
+        .or_else(|| rest.strip_prefix("+ "))?;
+    let rest = rest.strip_prefix('[')?;
+    let (mark, title) = rest.split_once(']')?;
+    let done = match mark {
+        " " => false,
+        "x" | "X" => true,
+        _ => return Some(Err(format!("unknown checkbox mark '[{}]'", mark))),
+    };
+    match title.trim() {
+        "" => Some(Err("missing description".to_string())),
+        title => Some(Ok((done, title))),
+    }
+}
+
+pub fn import(user: &mut User, text: &str, default_list: &str) -> ImportReport {
+    let mut report = ImportReport::default();
+    let mut list_name = default_list.to_string();
+    // The item that indented lines are added to as notes.
+    let mut last: Option<(String, usize)> = None;
+    for (index, line) in text.lines().enumerate() {
+        if let Some(name) = heading(line) {
+            list_name = name.to_string();
+            last = None;
+            continue;
+        }
+        match checkbox(line) {
+            Some(Ok((done, title))) => {
+                let mut todo = TodoItem::new(0, title.to_string());
+                todo.is_completed = done;
+                if done {
+                    todo.completed_at = Some(get_current_date_time());
+                }
+                let item_number = user.get_or_add_todo_list(&list_name).add_item(todo);
+                last = Some((list_name.clone(), item_number));
+                report.imported += 1;
+            }
+            Some(Err(e)) => report.errors.push((index + 1, e)),
+            None if line.trim().is_empty() => {}
+            None if line.starts_with([' ', '\t']) => {
+                if let Some((list_name, item_number)) = &last {
+                    let note = line.trim();
+                    let note = note
+                        .strip_prefix("- ")
+                        .or_else(|| note.strip_prefix("* "))
+                        .unwrap_or(note);
+                    let note = note
+                        .strip_prefix('\\')
+                        .filter(|rest| rest.starts_with(['[', '\\']))
+                        .unwrap_or(note);
+                    user.get_or_add_todo_list(list_name)
+                        .append_note(*item_number, note);
+                }
+            }
+            None => last = None,
+        }
+    }
+    report
+}
+
+pub fn export(user: &User, lists: &[&str]) -> String {
+    let mut sections = Vec::new();
+    for list_name in lists {
+        let mut section = format!("## {}\n\n", list_name);
+        for todo in user.todo_lists[*list_name]
+            .todos
+            .iter()
+            .filter(|t| !t.is_deleted)
+        {
+            let mark = if todo.is_completed { 'x' } else { ' ' };
+            section.push_str(&format!("- [{}] {}\n", mark, todo.title));
+            for line in todo.notes.lines().filter(|l| !l.trim().is_empty()) {
+                let escape = if line.starts_with(['[', '\\']) {
+                    "\\"
+                } else {
+                    ""
+                };
+                section.push_str(&format!("  - {}{}\n", escape, line));
+            }
+        }
+        sections.push(section);
+    }
+    sections.join("\n")
+}
@@ -1,4 +1,14 @@
+use crate::color::ColorChoice;
+use crate::exchange::ExchangeFormat;
+use crate::filter::parse_due;
+use crate::listing::{parse_sort_key, GroupBy, SortKey};
+use crate::notifications;
+use crate::output::{Column, Format};
+use crate::sharing::Role;
+use crate::stats::Period;
+use crate::timesheet::{parse_duration, TimesheetFormat};
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "todo")]
@@ -6,6 +16,9 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Colorize output; `auto` colors only a terminal and honours NO_COLOR.
+    #[arg(long, value_enum, global = true, default_value = "auto")]
+    pub color: ColorChoice,
 }
 
 #[derive(Subcommand)]
@@ -13,11 +26,31 @@ pub enum Commands {
     Show {
         #[arg(short, long)]
         all: bool,
-        #[arg(short, long)]
+        #[arg(short, long, conflicts_with = "incomplete")]
         completed: bool,
         #[arg(short, long)]
         incomplete: bool,
-        list_name: Option<String>,
+        /// A list name, or a filter such as 'list:work and not done and created>=-7d'.
+        filter: Option<String>,
+        /// Sort keys applied in order, e.g. `created:desc,title`.
+        #[arg(long, value_delimiter = ',', value_parser = parse_sort_key)]
+        sort: Vec<SortKey>,
+        /// Show archived items instead.
+        #[arg(long)]
+        archived: bool,
+        #[arg(long, value_enum, default_value = "list")]
+        group_by: GroupBy,
+        /// Table columns for text output, e.g. `id,title,done`.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        columns: Vec<Column>,
+        /// Only items assigned to you.
+        #[arg(long, conflicts_with = "assignee")]
+        mine: bool,
+        /// Only items assigned to this user.
+        #[arg(long)]
+        assignee: Option<String>,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
     },
     Add {
         list_name: String,
@@ -31,10 +64,217 @@ pub enum Commands {
         list_name: String,
         item_number: usize,
     },
+    Note {
+        list_name: String,
+        item_number: usize,
+        /// Text to append; opens $EDITOR on the whole note when omitted.
+        #[arg(trailing_var_arg = true)]
+        text: Vec<String>,
+    },
+    Due {
+        list_name: String,
+        item_number: usize,
+        /// A date such as `2024-06-01`, `tomorrow` or `+3d`; clears the due
+        /// date when omitted.
+        #[arg(value_parser = parse_due)]
+        date: Option<String>,
+    },
+    Assign {
+        list_name: String,
+        item_number: usize,
+        /// A user who can see the list; unassigns the item when omitted.
+        user: Option<String>,
+    },
+    Comment {
+        list_name: String,
+        item_number: usize,
+        /// The comment; `@name` notifies that user.
+        text: String,
+    },
+    Info {
+        list_name: String,
+        item_number: usize,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Open items with a due date, grouped by when they are due.
+    Agenda {
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    Search {
+        /// FTS5 query: words, `prefix*`, `'"a phrase"'`, AND/OR/NOT.
+        #[arg(required = true, trailing_var_arg = true)]
+        query: Vec<String>,
+        /// Search archived items instead.
+        #[arg(long)]
+        archived: bool,
+        /// Sort keys applied in order instead of best match first.
+        #[arg(long, value_delimiter = ',', value_parser = parse_sort_key)]
+        sort: Vec<SortKey>,
+        #[arg(long, value_enum, default_value = "none")]
+        group_by: GroupBy,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    Stats {
+        /// Count created and completed items per day, week or month.
+        #[arg(long, value_enum, default_value = "week")]
+        period: Period,
+        /// How many periods to show, ending with the current one.
+        #[arg(short = 'n', long, default_value_t = 8)]
+        periods: usize,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    Chart {
+        #[command(subcommand)]
+        kind: ChartKind,
+    },
+    Start {
+        list_name: String,
+        item_number: usize,
+    },
+    Stop,
+    LogTime {
+        /// How long, e.g. `1h30m`, `45m` or `2h`.
+        duration: String,
+        /// The item the time was spent on; defaults to the one with a running timer.
+        list_name: Option<String>,
+        item_number: Option<usize>,
+    },
+    Focus {
+        list_name: String,
+        item_number: usize,
+        /// Length of each pomodoro.
+        #[arg(long, default_value = "25m", value_parser = parse_duration)]
+        work: i64,
+        /// Length of the break after each pomodoro.
+        #[arg(long = "break", default_value = "5m", value_parser = parse_duration)]
+        rest: i64,
+        /// How many pomodoros to run.
+        #[arg(short = 'n', long, default_value_t = 1)]
+        rounds: usize,
+    },
+    Timesheet {
+        /// Only count time since the start of this week.
+        #[arg(long)]
+        week: bool,
+        #[arg(long, value_enum, default_value = "text")]
+        format: TimesheetFormat,
+        /// Write to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    Import {
+        file: PathBuf,
+        /// Format of the file; guessed from its extension when omitted.
+        #[arg(long, value_enum)]
+        from: Option<ExchangeFormat>,
+        /// List for items that don't name one.
+        #[arg(long, default_value = "Inbox")]
+        list: String,
+    },
+    Export {
+        #[arg(long, value_enum)]
+        format: ExchangeFormat,
+        /// Only export this list.
+        list_name: Option<String>,
+        /// Write to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    Dump {
+        /// Write to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    Restore {
+        /// A dump file to load into the database.
+        file: PathBuf,
+        /// Update users by name and items by id instead of replacing everything.
+        #[arg(long)]
+        merge: bool,
+    },
+    Trash {
+        #[command(subcommand)]
+        action: Option<TrashAction>,
+        /// Sort keys applied to each entry's first item instead of newest
+        /// first.
+        #[arg(long, value_delimiter = ',', value_parser = parse_sort_key)]
+        sort: Vec<SortKey>,
+        #[arg(long, value_enum, default_value = "none")]
+        group_by: GroupBy,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    Backup {
+        #[command(subcommand)]
+        action: Option<BackupAction>,
+    },
+    Archive {
+        list_name: String,
+        /// Archive the whole list; refused while any item is unfinished.
+        #[arg(long)]
+        all: bool,
+    },
+    Unarchive {
+        list_name: String,
+        item_number: Option<usize>,
+    },
     Remove {
         list_name: Option<String>,
         item_number: Option<usize>,
     },
+    Share {
+        list_name: String,
+        /// Who to share with; lists the members when omitted.
+        email: Option<String>,
+        #[arg(long, value_enum, default_value = "viewer")]
+        role: Role,
+    },
+    Unshare {
+        list_name: String,
+        email: String,
+    },
+    Notifications {
+        /// Only show notifications that haven't been read.
+        #[arg(long)]
+        unread: bool,
+        #[command(subcommand)]
+        action: Option<NotificationAction>,
+        /// `created`, `kind` or `status` keys applied in order instead of
+        /// newest first.
+        #[arg(long, value_delimiter = ',', value_parser = notifications::parse_sort_key)]
+        sort: Vec<SortKey<notifications::SortField>>,
+        #[arg(long, value_enum, default_value = "none")]
+        group_by: notifications::GroupBy,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    Undo {
+        /// How many operations to undo.
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+    Redo {
+        /// How many undone operations to redo.
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+    Log {
+        list_name: String,
+        item_number: usize,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    History {
+        /// How many operations to show.
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
     Push,
     Pull {
         user_name: String,
@@ -46,3 +286,116 @@ pub enum Commands {
     Logout,
     Exit,
 }
+
+impl Commands {
+    /// Whether the command can change items, and so is recorded for undo.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Commands::Add { .. }
+                | Commands::Complete { .. }
+                | Commands::Incomplete { .. }
+                | Commands::Note { .. }
+                | Commands::Due { .. }
+                | Commands::Assign { .. }
+                | Commands::Comment { .. }
+                | Commands::Import { .. }
+                | Commands::Archive { .. }
+                | Commands::Unarchive { .. }
+                | Commands::Remove { .. }
+                | Commands::Trash {
+                    action: Some(TrashAction::Empty | TrashAction::Restore { .. }),
+                    ..
+                }
+        )
+    }
+
+    /// The list a command changes, when it names one.
+    pub fn target_list(&self) -> Option<&str> {
+        match self {
+            Commands::Add { list_name, .. }
+            | Commands::Complete { list_name, .. }
+            | Commands::Incomplete { list_name, .. }
+            | Commands::Note { list_name, .. }
+            | Commands::Due { list_name, .. }
+            | Commands::Assign { list_name, .. }
+            | Commands::Comment { list_name, .. }
+            | Commands::Archive { list_name, .. }
+            | Commands::Unarchive { list_name, .. } => Some(list_name),
+            Commands::Remove { list_name, .. } => list_name.as_deref(),
+            Commands::Import { list, .. } => Some(list),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BackupAction {
+    /// List snapshots, newest first.
+    List,
+    /// Verify a snapshot and copy it over the database.
+    Restore {
+        /// A snapshot file name from `backup list`, or a path.
+        snapshot: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ChartKind {
+    /// Completions per day over the last year.
+    Heatmap,
+    /// Open and done items in a list per day.
+    Burndown {
+        list_name: String,
+        /// How many days to chart, ending today.
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NotificationAction {
+    /// Mark a notification, or all of them, as read.
+    MarkRead {
+        /// A notification number from `todo notifications`.
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        id: Option<i64>,
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// Delete everything in the trash for good.
+    Empty,
+    /// Put an entry back where it was.
+    Restore {
+        /// An entry number from `todo trash`.
+        id: usize,
+    },
+}
+
+/// Splits a REPL line into arguments, honouring single and double quotes.
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => args.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    args.extend(current);
+    Ok(args)
+}
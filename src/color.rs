@@ -0,0 +1,97 @@
+use crate::config::ThemeConfig;
+use clap::ValueEnum;
+use std::env;
+use std::io::{self, IsTerminal};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+const COLORS: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Turns a style such as `"bold red on_black"` into SGR parameters (`"1;31;40"`).
+pub fn parse_style(style: &str) -> Result<String, String> {
+    let mut codes = Vec::new();
+    for word in style.split_whitespace() {
+        let code = match word {
+            "bold" => 1,
+            "dim" => 2,
+            "italic" => 3,
+            "underline" => 4,
+            "reverse" => 7,
+            "strikethrough" => 9,
+            _ => {
+                let (base, name) = if let Some(name) = word.strip_prefix("on_") {
+                    (40, name)
+                } else if let Some(name) = word.strip_prefix("bright_") {
+                    (90, name)
+                } else {
+                    (30, word)
+                };
+                match COLORS.iter().position(|color| *color == name) {
+                    Some(index) => base + index,
+                    None => return Err(format!("unknown style '{}' in \"{}\"", word, style)),
+                }
+            }
+        };
+        codes.push(code.to_string());
+    }
+    Ok(codes.join(";"))
+}
+
+/// SGR parameters for each themed element.
+#[derive(Debug, Default)]
+pub struct Theme {
+    pub header: String,
+    pub group: String,
+    pub done: String,
+    pub highlight: String,
+    pub priority_high: String,
+    pub overdue: String,
+}
+
+impl Theme {
+    pub fn from_config(config: &ThemeConfig) -> Result<Self, String> {
+        Ok(Theme {
+            header: parse_style(&config.header)?,
+            group: parse_style(&config.group)?,
+            done: parse_style(&config.done)?,
+            highlight: parse_style(&config.highlight)?,
+            priority_high: parse_style(&config.priority_high)?,
+            overdue: parse_style(&config.overdue)?,
+        })
+    }
+}
+
+/// Applies the theme when color is enabled, otherwise passes text through.
+pub struct Painter<'a> {
+    theme: Option<&'a Theme>,
+}
+
+impl<'a> Painter<'a> {
+    pub fn new(choice: ColorChoice, theme: &'a Theme) -> Self {
+        let enabled = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && io::stdout().is_terminal()
+            }
+        };
+        Painter {
+            theme: enabled.then_some(theme),
+        }
+    }
+
+    pub fn paint(&self, text: &str, style: impl Fn(&Theme) -> &str) -> String {
+        match self.theme.map(style) {
+            Some(codes) if !codes.is_empty() => format!("\x1b[{}m{}\x1b[0m", codes, text),
+            _ => text.to_string(),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Comment threads on items. Comments are kept on the item in memory and
+//! written to the `comments` table on push, keyed by their own uid so pushing
+//! twice doesn't repeat them. They can't be edited or deleted, except along
+//! with their item.
+
+use crate::notifications;
+use crate::sharing;
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub uid: String,
+    pub author: String,
+    pub created_at: String,
+    pub text: String,
+}
+
+/// The user names mentioned as `@name` in a comment, in order and without
+/// repeats. A name is letters, digits, `_` and `-`, so `@bob,` mentions `bob`.
+pub fn mentions(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(name) = word.strip_prefix('@') else {
+            continue;
+        };
+        let name: String = name
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Writes a comment on the item with `item_uid`, returning whether it is new.
+pub fn save(conn: &Connection, item_uid: &str, comment: &Comment) -> Result<bool> {
+    let added = conn.execute(
+        "INSERT OR IGNORE INTO comments (uid, item_uid, author, created_at, text)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            comment.uid,
+            item_uid,
+            comment.author,
+            comment.created_at,
+            comment.text
+        ],
+    )?;
+    Ok(added > 0)
+}
+
+/// Whether a comment has been pushed.
+pub fn exists(conn: &Connection, uid: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM comments WHERE uid = ?1)",
+        params![uid],
+        |row| row.get(0),
+    )
+}
+
+/// An item's comments, oldest first.
+pub fn load(conn: &Connection, item_uid: &str) -> Result<Vec<Comment>> {
+    let mut stmt = conn.prepare(
+        "SELECT uid, author, created_at, text FROM comments
+        WHERE item_uid = ?1 ORDER BY created_at, id",
+    )?;
+    let comments = stmt.query_map(params![item_uid], |row| {
+        Ok(Comment {
+            uid: row.get(0)?,
+            author: row.get(1)?,
+            created_at: row.get(2)?,
+            text: row.get(3)?,
+        })
+    })?;
+    comments.collect()
+}
+
+/// Notifies the users mentioned in a new comment on an item of `owner`'s
+/// list. Only users who can see the list are told, and never the author.
+pub fn notify_mentions(
+    conn: &Connection,
+    owner: &str,
+    list_name: &str,
+    title: &str,
+    comment: &Comment,
+) -> Result<()> {
+    for name in mentions(&comment.text) {
+        if name == comment.author || !sharing::has_access(conn, owner, list_name, &name)? {
+            continue;
+        }
+        let list = sharing::seen_as(owner, list_name, &name);
+        let message = format!(
+            "{} mentioned you on \"{}\" in {}: {}",
+            comment.author, title, list, comment.text
+        );
+        notifications::notify(conn, &name, "mention", &message)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Settings read from `$TODO_CONFIG`, or `todo/config.toml` under
+/// `$XDG_CONFIG_HOME` (default `~/.config`). Every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub theme: ThemeConfig,
+    pub backup: BackupConfig,
+    pub trash: TrashConfig,
+    pub archive: ArchiveConfig,
+}
+
+/// Styles are space-separated words such as `"bold red"` or `"dim strikethrough"`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub header: String,
+    pub group: String,
+    pub done: String,
+    pub highlight: String,
+    /// Open items with priority A.
+    pub priority_high: String,
+    /// Open items due before today.
+    pub overdue: String,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig {
+            header: "bold underline".to_string(),
+            group: "bold cyan".to_string(),
+            done: "dim strikethrough".to_string(),
+            highlight: "bold yellow".to_string(),
+            priority_high: "bold red".to_string(),
+            overdue: "red".to_string(),
+        }
+    }
+}
+
+/// Where `todo backup` writes snapshots and how many it keeps: the newest of
+/// each of the last `keep_daily` days and `keep_weekly` weeks.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Defaults to a `backups` directory next to the database.
+    pub dir: Option<PathBuf>,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            dir: None,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// Deleted items are purged for good after this many days; 0 keeps them.
+    pub purge_after_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            purge_after_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    /// Completed items are archived this many days after completion; 0 never
+    /// archives them automatically.
+    pub after_days: u32,
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var("TODO_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok()?;
+    Some(base.join("todo").join("config.toml"))
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    toml::from_str(text).map_err(|e| e.to_string())
+}
+
+/// Loads the config file, falling back to the defaults when there is none.
+pub fn load() -> Result<Config, String> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
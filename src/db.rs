@@ -1,32 +1,195 @@
-use rusqlite::{Connection, Result};
 use dotenv::dotenv;
+use rusqlite::{Connection, Result};
 use std::env;
 
+// Each entry upgrades the schema by one version; the current version is kept
+// in `PRAGMA user_version`. Only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS users (
+        id INTEGER PRIMARY KEY,
+        user_name TEXT NOT NULL UNIQUE,
+        email TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS todos (
+        id INTEGER PRIMARY KEY,
+        user_id INTEGER NOT NULL,
+        list_name TEXT NOT NULL,
+        item_number INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        datetime TEXT NOT NULL,
+        is_completed INTEGER NOT NULL,
+        FOREIGN KEY(user_id) REFERENCES users(id)
+    );",
+    // `push_to_db` upserts items, which needs a key to conflict on.
+    "DELETE FROM todos WHERE id NOT IN (
+        SELECT MIN(id) FROM todos GROUP BY user_id, list_name, item_number
+    );
+    CREATE UNIQUE INDEX IF NOT EXISTS todos_item ON todos (user_id, list_name, item_number);",
+    "ALTER TABLE todos ADD COLUMN notes TEXT NOT NULL DEFAULT '';",
+    "CREATE VIRTUAL TABLE todos_fts USING fts5(
+        title, notes, content = 'todos', content_rowid = 'id'
+    );
+    INSERT INTO todos_fts (todos_fts) VALUES ('rebuild');
+    CREATE TRIGGER todos_fts_insert AFTER INSERT ON todos BEGIN
+        INSERT INTO todos_fts (rowid, title, notes) VALUES (new.id, new.title, new.notes);
+    END;
+    CREATE TRIGGER todos_fts_delete AFTER DELETE ON todos BEGIN
+        INSERT INTO todos_fts (todos_fts, rowid, title, notes)
+        VALUES ('delete', old.id, old.title, old.notes);
+    END;
+    CREATE TRIGGER todos_fts_update AFTER UPDATE ON todos BEGIN
+        INSERT INTO todos_fts (todos_fts, rowid, title, notes)
+        VALUES ('delete', old.id, old.title, old.notes);
+        INSERT INTO todos_fts (rowid, title, notes) VALUES (new.id, new.title, new.notes);
+    END;",
+    "ALTER TABLE todos ADD COLUMN completed_at TEXT;
+    ALTER TABLE todos ADD COLUMN priority TEXT;
+    ALTER TABLE todos ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+    DROP TRIGGER todos_fts_insert;
+    DROP TRIGGER todos_fts_delete;
+    DROP TRIGGER todos_fts_update;
+    DROP TABLE todos_fts;
+    CREATE VIRTUAL TABLE todos_fts USING fts5(
+        title, notes, tags, content = 'todos', content_rowid = 'id'
+    );
+    INSERT INTO todos_fts (todos_fts) VALUES ('rebuild');
+    CREATE TRIGGER todos_fts_insert AFTER INSERT ON todos BEGIN
+        INSERT INTO todos_fts (rowid, title, notes, tags)
+        VALUES (new.id, new.title, new.notes, new.tags);
+    END;
+    CREATE TRIGGER todos_fts_delete AFTER DELETE ON todos BEGIN
+        INSERT INTO todos_fts (todos_fts, rowid, title, notes, tags)
+        VALUES ('delete', old.id, old.title, old.notes, old.tags);
+    END;
+    CREATE TRIGGER todos_fts_update AFTER UPDATE ON todos BEGIN
+        INSERT INTO todos_fts (todos_fts, rowid, title, notes, tags)
+        VALUES ('delete', old.id, old.title, old.notes, old.tags);
+        INSERT INTO todos_fts (rowid, title, notes, tags)
+        VALUES (new.id, new.title, new.notes, new.tags);
+    END;",
+    "ALTER TABLE todos ADD COLUMN uid TEXT;
+    UPDATE todos SET uid = lower(
+        hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-' || hex(randomblob(2)) || '-' ||
+        hex(randomblob(2)) || '-' || hex(randomblob(6))
+    );
+    CREATE UNIQUE INDEX todos_uid ON todos (uid);",
+    "ALTER TABLE todos ADD COLUMN extra TEXT NOT NULL DEFAULT '';",
+    // Keyed by name rather than user id: operations are recorded before the
+    // user has ever been pushed.
+    "CREATE TABLE journal (
+        id INTEGER PRIMARY KEY,
+        user_name TEXT NOT NULL,
+        at TEXT NOT NULL,
+        command TEXT NOT NULL,
+        changes TEXT NOT NULL,
+        undone INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX journal_user ON journal (user_name, id);",
+    "CREATE TABLE todo_events (
+        id INTEGER PRIMARY KEY,
+        uid TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        list_name TEXT NOT NULL,
+        item_number INTEGER NOT NULL,
+        actor TEXT NOT NULL,
+        at TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        field TEXT,
+        old_value TEXT,
+        new_value TEXT
+    );
+    CREATE INDEX todo_events_uid ON todo_events (uid, id);
+    CREATE INDEX todo_events_item ON todo_events (owner, list_name, item_number);
+    CREATE TRIGGER todo_events_no_update BEFORE UPDATE ON todo_events BEGIN
+        SELECT RAISE(ABORT, 'todo_events is append-only');
+    END;
+    CREATE TRIGGER todo_events_no_delete BEFORE DELETE ON todo_events BEGIN
+        SELECT RAISE(ABORT, 'todo_events is append-only');
+    END;",
+    "ALTER TABLE todos ADD COLUMN trash_id INTEGER;
+    ALTER TABLE todos ADD COLUMN deleted_at TEXT;
+    ALTER TABLE todos ADD COLUMN deleted_by TEXT;",
+    "ALTER TABLE todos ADD COLUMN archived_at TEXT;",
+    "CREATE TABLE time_entries (
+        id INTEGER PRIMARY KEY,
+        user_name TEXT NOT NULL,
+        uid TEXT NOT NULL,
+        list_name TEXT NOT NULL,
+        item_number INTEGER NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT
+    );
+    CREATE INDEX time_entries_user ON time_entries (user_name, started_at);
+    CREATE UNIQUE INDEX time_entries_running ON time_entries (user_name) WHERE ended_at IS NULL;",
+    "ALTER TABLE time_entries ADD COLUMN pomodoro INTEGER NOT NULL DEFAULT 0;",
+    "CREATE TABLE list_members (
+        owner_id INTEGER NOT NULL REFERENCES users (id),
+        list_name TEXT NOT NULL,
+        member_id INTEGER NOT NULL REFERENCES users (id),
+        role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+        PRIMARY KEY (owner_id, list_name, member_id)
+    );
+    CREATE INDEX list_members_member ON list_members (member_id);",
+    "CREATE TABLE notifications (
+        id INTEGER PRIMARY KEY,
+        user_name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        message TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        read_at TEXT
+    );
+    CREATE INDEX notifications_user ON notifications (user_name, read_at);",
+    "ALTER TABLE todos ADD COLUMN assignee_id INTEGER REFERENCES users (id);",
+    "CREATE TABLE comments (
+        id INTEGER PRIMARY KEY,
+        uid TEXT NOT NULL UNIQUE,
+        item_uid TEXT NOT NULL,
+        author TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        text TEXT NOT NULL
+    );
+    CREATE INDEX comments_item ON comments (item_uid);",
+    "ALTER TABLE todos ADD COLUMN due TEXT;",
+    // Set once the item's owner or assignee has been told it is overdue, and
+    // cleared whenever the due date changes.
+    "ALTER TABLE todos ADD COLUMN due_notified INTEGER NOT NULL DEFAULT 0;",
+    // Events recorded as changes are made, moved to todo_events when the
+    // actor pushes them and dropped when they pull over them instead.
+    "CREATE TABLE pending_events (
+        id INTEGER PRIMARY KEY,
+        uid TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        list_name TEXT NOT NULL,
+        item_number INTEGER NOT NULL,
+        actor TEXT NOT NULL,
+        at TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        field TEXT,
+        old_value TEXT,
+        new_value TEXT
+    );
+    CREATE INDEX pending_events_actor ON pending_events (actor, uid);",
+];
+
+/// The `user_version` of a fully migrated database.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
 
 pub fn establish_connection() -> Result<Connection> {
     dotenv().ok();
     let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let conn = Connection::open(url)?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY,
-            user_name TEXT NOT NULL UNIQUE,
-            email TEXT NOT NULL
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS todos (
-            id INTEGER PRIMARY KEY,
-            user_id INTEGER NOT NULL,
-            list_name TEXT NOT NULL,
-            item_number INTEGER NOT NULL,
-            title TEXT NOT NULL,
-            datetime TEXT NOT NULL,
-            is_completed INTEGER NOT NULL,
-            FOREIGN KEY(user_id) REFERENCES users(id)
-        )",
-        [],
-    )?;
+    migrate(&conn)?;
     Ok(conn)
 }
+
+pub fn migrate(conn: &Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration,
+            index + 1
+        ))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,492 @@
+//! Portable JSON copies of the whole database.
+//!
+//! A dump holds every user, list and item along with the schema version it
+//! was written at. Fields added by later migrations are optional, so older
+//! dumps still restore; dumps from a newer schema are rejected.
+
+use crate::comments::{self, Comment};
+use crate::db::{migrate, SCHEMA_VERSION};
+use crate::events;
+use crate::journal::Snapshot;
+use crate::sharing;
+use crate::todo::{TodoItem, Trash};
+use rusqlite::backup::Backup;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+pub struct Dump {
+    pub version: usize,
+    pub users: Vec<DumpUser>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DumpUser {
+    pub user_name: String,
+    pub email: String,
+    pub lists: Vec<DumpList>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DumpList {
+    pub name: String,
+    pub items: Vec<DumpItem>,
+    /// Other users the list is shared with.
+    #[serde(default)]
+    pub members: Vec<DumpMember>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DumpMember {
+    pub user_name: String,
+    pub role: String,
+}
+
+fn new_uid() -> String {
+    Uuid::new_v4().to_string()
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpItem {
+    #[serde(default = "new_uid")]
+    pub uid: String,
+    pub item: usize,
+    pub title: String,
+    pub created: String,
+    pub completed: bool,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub priority: Option<char>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub extra: Map<String, Value>,
+    #[serde(default)]
+    pub trash: Option<Trash>,
+    #[serde(default)]
+    pub archived_at: Option<String>,
+    /// The user name the item is assigned to.
+    #[serde(default)]
+    pub assignee: Option<String>,
+    /// Oldest first.
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+}
+
+impl DumpItem {
+    pub fn from_item(todo: &TodoItem) -> Self {
+        DumpItem {
+            uid: todo.uid.clone(),
+            item: todo.item_number,
+            title: todo.title.clone(),
+            created: todo.datetime.clone(),
+            completed: todo.is_completed,
+            completed_at: todo.completed_at.clone(),
+            due: todo.due.clone(),
+            priority: todo.priority,
+            tags: todo.tags.clone(),
+            notes: todo.notes.clone(),
+            extra: todo.extra.clone(),
+            trash: todo.trash.clone(),
+            archived_at: todo.archived_at.clone(),
+            assignee: todo.assignee.clone(),
+            comments: todo.comments.clone(),
+        }
+    }
+
+    /// Overwrites everything but the item number.
+    pub fn apply(&self, todo: &mut TodoItem) {
+        todo.uid = self.uid.clone();
+        todo.title = self.title.clone();
+        todo.datetime = self.created.clone();
+        todo.is_completed = self.completed;
+        todo.completed_at = self.completed_at.clone();
+        todo.due = self.due.clone();
+        todo.priority = self.priority;
+        todo.tags = self.tags.clone();
+        todo.notes = self.notes.clone();
+        todo.extra = self.extra.clone();
+        todo.trash = self.trash.clone();
+        todo.is_deleted = self.trash.is_some();
+        todo.archived_at = self.archived_at.clone();
+        todo.assignee = self.assignee.clone();
+        todo.comments = self.comments.clone();
+        todo.is_modified = true;
+    }
+
+    pub fn to_item(&self) -> TodoItem {
+        let mut todo = TodoItem::new(self.item, String::new());
+        self.apply(&mut todo);
+        todo
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RestoreReport {
+    pub users: usize,
+    pub inserted: usize,
+    pub updated: usize,
+}
+
+/// The columns [`item_from_row`] reads, and the tables they come from.
+const ITEM_QUERY: &str = "t.list_name, t.item_number, t.uid, t.title, t.datetime, t.is_completed,
+        t.completed_at, t.priority, t.tags, t.notes, t.extra, t.trash_id, t.deleted_at,
+        t.deleted_by, t.archived_at, a.user_name, t.due
+    FROM todos t LEFT JOIN users a ON a.id = t.assignee_id";
+
+/// An item and its list name from an [`ITEM_QUERY`] row, without comments.
+fn item_from_row(row: &Row) -> Result<(String, DumpItem)> {
+    let trash = match row.get::<_, Option<usize>>(11)? {
+        Some(id) => Some(Trash {
+            id,
+            deleted_at: row.get(12)?,
+            deleted_by: row.get(13)?,
+        }),
+        None => None,
+    };
+    Ok((
+        row.get::<_, String>(0)?,
+        DumpItem {
+            item: row.get(1)?,
+            uid: row.get::<_, Option<String>>(2)?.unwrap_or_else(new_uid),
+            title: row.get(3)?,
+            created: row.get(4)?,
+            completed: row.get::<_, i32>(5)? != 0,
+            completed_at: row.get(6)?,
+            due: row.get(16)?,
+            priority: row
+                .get::<_, Option<String>>(7)?
+                .and_then(|p| p.chars().next()),
+            tags: row
+                .get::<_, String>(8)?
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            notes: row.get(9)?,
+            extra: serde_json::from_str(&row.get::<_, String>(10)?).unwrap_or_default(),
+            trash,
+            archived_at: row.get(14)?,
+            assignee: row.get(15)?,
+            comments: Vec::new(),
+        },
+    ))
+}
+
+/// An item as it is stored now, with its list name; `None` if it isn't.
+pub fn stored(conn: &Connection, uid: &str) -> Result<Option<(String, DumpItem)>> {
+    let found = conn
+        .query_row(
+            &format!("SELECT {} WHERE t.uid = ?1", ITEM_QUERY),
+            params![uid],
+            item_from_row,
+        )
+        .optional()?;
+    let Some((list_name, mut item)) = found else {
+        return Ok(None);
+    };
+    item.comments = comments::load(conn, uid)?;
+    Ok(Some((list_name, item)))
+}
+
+/// Reads every user, list and item in the database.
+pub fn dump(conn: &Connection) -> Result<Dump> {
+    let mut users = Vec::new();
+    let mut stmt = conn.prepare("SELECT id, user_name, email FROM users ORDER BY user_name")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    let mut items = conn.prepare(&format!(
+        "SELECT {} WHERE t.user_id = ?1 ORDER BY t.list_name, t.item_number",
+        ITEM_QUERY
+    ))?;
+    for row in rows {
+        let (user_id, user_name, email) = row?;
+        let mut lists: Vec<DumpList> = Vec::new();
+        let item_rows = items.query_map(params![user_id], item_from_row)?;
+        for item_row in item_rows {
+            let (list_name, mut item) = item_row?;
+            item.comments = comments::load(conn, &item.uid)?;
+            match lists.last_mut() {
+                Some(list) if list.name == list_name => list.items.push(item),
+                _ => lists.push(DumpList {
+                    name: list_name,
+                    items: vec![item],
+                    members: Vec::new(),
+                }),
+            }
+        }
+        for list in &mut lists {
+            list.members = sharing::members(conn, &user_name, &list.name)?
+                .into_iter()
+                .map(|member| DumpMember {
+                    user_name: member.user_name,
+                    role: member.role.as_str().to_string(),
+                })
+                .collect();
+        }
+        users.push(DumpUser {
+            user_name,
+            email,
+            lists,
+        });
+    }
+    Ok(Dump {
+        version: SCHEMA_VERSION,
+        users,
+    })
+}
+
+/// Parses a dump, rejecting ones written by a newer schema.
+pub fn parse(text: &str) -> std::result::Result<Dump, String> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: usize,
+    }
+    // Check the version first: a newer dump may not deserialize at all.
+    let version = serde_json::from_str::<Version>(text)
+        .map_err(|e| e.to_string())?
+        .version;
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "dump is from schema version {}, but this build only supports up to {}",
+            version, SCHEMA_VERSION
+        ));
+    }
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+/// The uid to write a user's item under: its own, or a fresh one when
+/// another user's item already has it.
+fn uid_for(conn: &Connection, user_id: i64, uid: &str) -> Result<String> {
+    let holder: Option<i64> = conn
+        .query_row(
+            "SELECT user_id FROM todos WHERE uid = ?1",
+            params![uid],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(match holder {
+        Some(holder) if holder != user_id => new_uid(),
+        _ => uid.to_string(),
+    })
+}
+
+fn upsert_item(
+    conn: &Connection,
+    user_id: i64,
+    list_name: &str,
+    item: &DumpItem,
+    report: &mut RestoreReport,
+) -> Result<()> {
+    let extra = if item.extra.is_empty() {
+        String::new()
+    } else {
+        Value::Object(item.extra.clone()).to_string()
+    };
+    let updated = conn.execute(
+        "UPDATE todos SET title = ?2, datetime = ?3, is_completed = ?4, completed_at = ?5,
+            priority = ?6, tags = ?7, notes = ?8, extra = ?9, trash_id = ?10, deleted_at = ?11,
+            deleted_by = ?12, archived_at = ?13,
+            due_notified = CASE WHEN due IS ?14 THEN due_notified ELSE 0 END, due = ?14
+        WHERE uid = ?1",
+        params![
+            item.uid,
+            item.title,
+            item.created,
+            item.completed as i32,
+            item.completed_at,
+            item.priority.map(String::from),
+            item.tags.join(" "),
+            item.notes,
+            extra,
+            item.trash.as_ref().map(|trash| trash.id),
+            item.trash.as_ref().map(|trash| &trash.deleted_at),
+            item.trash.as_ref().map(|trash| &trash.deleted_by),
+            item.archived_at,
+            item.due
+        ],
+    )?;
+    if updated > 0 {
+        report.updated += 1;
+        return Ok(());
+    }
+    // Keep the item's number unless another item already has it.
+    let taken = conn
+        .query_row(
+            "SELECT 1 FROM todos WHERE user_id = ?1 AND list_name = ?2 AND item_number = ?3",
+            params![user_id, list_name, item.item],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    let item_number: usize = if taken {
+        conn.query_row(
+            "SELECT MAX(item_number) + 1 FROM todos WHERE user_id = ?1 AND list_name = ?2",
+            params![user_id, list_name],
+            |row| row.get(0),
+        )?
+    } else {
+        item.item
+    };
+    conn.execute(
+        "INSERT INTO todos (user_id, list_name, item_number, uid, title, datetime, is_completed,
+            completed_at, priority, tags, notes, extra, trash_id, deleted_at, deleted_by,
+            archived_at, due)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        params![
+            user_id,
+            list_name,
+            item_number,
+            item.uid,
+            item.title,
+            item.created,
+            item.completed as i32,
+            item.completed_at,
+            item.priority.map(String::from),
+            item.tags.join(" "),
+            item.notes,
+            extra,
+            item.trash.as_ref().map(|trash| trash.id),
+            item.trash.as_ref().map(|trash| &trash.deleted_at),
+            item.trash.as_ref().map(|trash| &trash.deleted_by),
+            item.archived_at,
+            item.due
+        ],
+    )?;
+    report.inserted += 1;
+    Ok(())
+}
+
+/// Loads a dump into the database. Without `merge` the database is replaced
+/// by a freshly migrated one holding only the dump; with it, users are matched
+/// by name and items by uid, and nothing is deleted. Changed items get events
+/// by the `restore` actor.
+pub fn restore(conn: &mut Connection, dump: &Dump, merge: bool) -> Result<RestoreReport> {
+    if merge {
+        return write(conn, dump, conn);
+    }
+    // Build the replacement from scratch so nothing keyed by a uid or user
+    // name (undo history, time, notifications) outlives the restore. The
+    // audit trail is append-only, so it's carried over, and the restore's
+    // own events are against what the database held before.
+    let old = self::dump(conn)?;
+    let fresh = Connection::open_in_memory()?;
+    migrate(&fresh)?;
+    copy_events(conn, &fresh)?;
+    let report = write(&fresh, dump, conn)?;
+    // Items the dump doesn't have are gone for good.
+    for user in &old.users {
+        let mut gone = Snapshot::new();
+        for list in &user.lists {
+            for item in &list.items {
+                if stored(&fresh, &item.uid)?.is_none() {
+                    gone.insert(item.uid.clone(), (list.name.clone(), item.clone()));
+                }
+            }
+        }
+        events::record(&fresh, &user.user_name, "restore", &gone, &Snapshot::new())?;
+    }
+    Backup::new(&fresh, conn)?.run_to_completion(64, std::time::Duration::ZERO, None)?;
+    Ok(report)
+}
+
+/// Copies every row of `from`'s audit trail into `to`, keeping ids and order.
+fn copy_events(from: &Connection, to: &Connection) -> Result<()> {
+    let columns = "id, uid, owner, list_name, item_number, actor, at, kind, field, old_value,
+        new_value";
+    let mut stmt = from.prepare(&format!("SELECT {} FROM todo_events ORDER BY id", columns))?;
+    let mut rows = stmt.query([])?;
+    let tx = to.unchecked_transaction()?;
+    while let Some(row) = rows.next()? {
+        let values: Vec<rusqlite::types::Value> =
+            (0..11).map(|i| row.get(i)).collect::<Result<_>>()?;
+        tx.execute(
+            &format!(
+                "INSERT INTO todo_events ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                columns
+            ),
+            rusqlite::params_from_iter(values),
+        )?;
+    }
+    tx.commit()
+}
+
+/// Writes a dump in one transaction, recording events against the items as
+/// `old` holds them.
+fn write(conn: &Connection, dump: &Dump, old: &Connection) -> Result<RestoreReport> {
+    let tx = conn.unchecked_transaction()?;
+    let mut report = RestoreReport::default();
+    // The uid each item was written under, in dump order.
+    let mut uids = Vec::new();
+    for user in &dump.users {
+        tx.execute(
+            "INSERT INTO users (user_name, email) VALUES (?1, ?2)
+            ON CONFLICT (user_name) DO UPDATE SET email = excluded.email",
+            params![user.user_name, user.email],
+        )?;
+        let user_id: i64 = tx.query_row(
+            "SELECT id FROM users WHERE user_name = ?1",
+            params![user.user_name],
+            |row| row.get(0),
+        )?;
+        let mut before = Snapshot::new();
+        let mut after = Snapshot::new();
+        for list in &user.lists {
+            for item in &list.items {
+                let item = DumpItem {
+                    uid: uid_for(&tx, user_id, &item.uid)?,
+                    ..item.clone()
+                };
+                if let Some(state) = stored(old, &item.uid)? {
+                    before.insert(item.uid.clone(), state);
+                }
+                upsert_item(&tx, user_id, &list.name, &item, &mut report)?;
+                if let Some(state) = stored(&tx, &item.uid)? {
+                    after.insert(item.uid.clone(), state);
+                }
+                uids.push(item.uid);
+            }
+        }
+        events::record(&tx, &user.user_name, "restore", &before, &after)?;
+        report.users += 1;
+    }
+    // Members and assignees may be users restored after the list's owner.
+    let mut uids = uids.iter();
+    for user in &dump.users {
+        for list in &user.lists {
+            for item in &list.items {
+                let uid = uids.next().unwrap();
+                tx.execute(
+                    "UPDATE todos SET assignee_id = (SELECT id FROM users WHERE user_name = ?2)
+                    WHERE uid = ?1",
+                    params![uid, item.assignee],
+                )?;
+                for comment in &item.comments {
+                    comments::save(&tx, uid, comment)?;
+                }
+            }
+            for member in &list.members {
+                tx.execute(
+                    "INSERT INTO list_members (owner_id, list_name, member_id, role)
+                    SELECT o.id, ?2, m.id, ?4 FROM users o, users m
+                    WHERE o.user_name = ?1 AND m.user_name = ?3
+                    ON CONFLICT (owner_id, list_name, member_id) DO UPDATE SET role = excluded.role",
+                    params![user.user_name, list.name, member.user_name, member.role],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(report)
+}
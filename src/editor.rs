@@ -0,0 +1,35 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Error, ErrorKind, Write};
+use std::process::Command;
+use uuid::Uuid;
+
+/// Opens `$VISUAL`/`$EDITOR` (falling back to `vi`) on a temp file holding
+/// `initial` and returns the saved contents once the editor exits.
+pub fn edit_text(initial: &str) -> io::Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "$EDITOR is empty"))?;
+
+    // A random name, and refusing to open a file that is already there, so
+    // another user can't plant a file or symlink for us to write through.
+    let path = env::temp_dir().join(format!("todo-note-{}.md", Uuid::new_v4()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?
+        .write_all(initial.as_bytes())?;
+
+    let status = Command::new(program).args(parts).arg(&path).status();
+    let result = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path),
+        Ok(status) => Err(Error::other(format!("{} exited with {}", editor, status))),
+        Err(e) => Err(e),
+    };
+    let _ = fs::remove_file(&path);
+    result
+}
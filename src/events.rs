@@ -0,0 +1,291 @@
+//! The audit trail: an append-only `todo_events` table with one row per
+//! change to an item, saying who made it, when, and the old and new values.
+//! Rows are keyed by the item's uid, so an item's timeline survives deletes
+//! and moves; `owner` is the user whose lists the item is in.
+//!
+//! Changes made in a session are [`queue`]d in `pending_events` when they
+//! happen, with the time they happened, and [`flush`]ed into `todo_events`
+//! when they are pushed; a pull throws them away along with the changes.
+
+use crate::dump::DumpItem;
+use crate::journal::Snapshot;
+use crate::todo::{get_current_date_time, User};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+const COLUMNS: &str =
+    "uid, owner, list_name, item_number, actor, at, kind, field, old_value, new_value";
+
+#[derive(Serialize)]
+pub struct Event {
+    pub actor: String,
+    pub at: String,
+    /// `create`, `edit`, `complete`, `incomplete`, `move`, `delete` (to the
+    /// trash, or for good when it was never pushed), `restore`, `purge`,
+    /// `archive` or `unarchive`.
+    pub kind: String,
+    /// The edited field, for `edit` events.
+    pub field: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// Whether the change has been pushed yet.
+    pub pushed: bool,
+}
+
+impl Event {
+    /// A one-line description such as `edited title: "a" -> "b"`.
+    pub fn describe(&self) -> String {
+        let quoted = |value: &Option<String>| format!("{:?}", value.as_deref().unwrap_or(""));
+        match (self.kind.as_str(), &self.field) {
+            ("create", _) => format!("created {}", quoted(&self.new_value)),
+            ("delete", _) => format!("deleted {}", quoted(&self.old_value)),
+            ("restore", _) => format!("restored {} from the trash", quoted(&self.new_value)),
+            ("purge", _) => format!("purged {} from the trash", quoted(&self.old_value)),
+            ("move", _) => format!(
+                "moved from {} to {}",
+                quoted(&self.old_value),
+                quoted(&self.new_value)
+            ),
+            ("edit", Some(field)) => format!(
+                "edited {}: {} -> {}",
+                field,
+                quoted(&self.old_value),
+                quoted(&self.new_value)
+            ),
+            ("archive", _) => "archived".to_string(),
+            ("unarchive", _) => "unarchived".to_string(),
+            ("complete", _) => "completed".to_string(),
+            ("incomplete", _) => "marked incomplete".to_string(),
+            (kind, _) => kind.to_string(),
+        }
+    }
+}
+
+type Values = (
+    &'static str,
+    Option<&'static str>,
+    Option<String>,
+    Option<String>,
+);
+
+/// The edited fields between two states of an item, with old and new values.
+fn edits(old: &DumpItem, new: &DumpItem) -> Vec<Values> {
+    let fields = [
+        ("title", Some(old.title.clone()), Some(new.title.clone())),
+        ("notes", Some(old.notes.clone()), Some(new.notes.clone())),
+        (
+            "created",
+            Some(old.created.clone()),
+            Some(new.created.clone()),
+        ),
+        (
+            "priority",
+            old.priority.map(String::from),
+            new.priority.map(String::from),
+        ),
+        ("tags", Some(old.tags.join(" ")), Some(new.tags.join(" "))),
+        ("due", old.due.clone(), new.due.clone()),
+        ("assignee", old.assignee.clone(), new.assignee.clone()),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| ("edit", Some(field), old, new))
+        .collect()
+}
+
+/// Writes an event for every difference between two snapshots of `owner`'s
+/// lists, made by `actor`.
+pub fn record(
+    conn: &Connection,
+    owner: &str,
+    actor: &str,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> Result<()> {
+    write(conn, "todo_events", owner, actor, before, after)
+}
+
+/// Queues an event for every difference between two snapshots of the user's
+/// lists until they push. Items in shared lists are filed under the owner
+/// and the list's own name.
+pub fn queue(conn: &Connection, user: &User, before: &Snapshot, after: &Snapshot) -> Result<()> {
+    let mut by_owner: BTreeMap<&str, (Snapshot, Snapshot)> = BTreeMap::new();
+    for (side, snapshot) in [(0, before), (1, after)] {
+        for (uid, (list_name, item)) in snapshot {
+            let (owner, name) = user
+                .origin(list_name)
+                .unwrap_or((&user.user_name, list_name));
+            let snapshots = by_owner.entry(owner).or_default();
+            let snapshot = if side == 0 {
+                &mut snapshots.0
+            } else {
+                &mut snapshots.1
+            };
+            snapshot.insert(uid.clone(), (name.to_string(), item.clone()));
+        }
+    }
+    for (owner, (before, after)) in by_owner {
+        write(
+            conn,
+            "pending_events",
+            owner,
+            &user.user_name,
+            &before,
+            &after,
+        )?;
+    }
+    Ok(())
+}
+
+/// Points `actor`'s queued events for an item at the uid and number push
+/// stored it under.
+pub fn rekey(
+    conn: &Connection,
+    actor: &str,
+    old_uid: &str,
+    uid: &str,
+    item_number: usize,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE pending_events SET uid = ?3, item_number = ?4 WHERE actor = ?1 AND uid = ?2",
+        params![actor, old_uid, uid, item_number],
+    )?;
+    Ok(())
+}
+
+/// Moves `actor`'s queued events into the audit trail, in the order they
+/// happened.
+pub fn flush(conn: &Connection, actor: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO todo_events ({0}) SELECT {0} FROM pending_events
+            WHERE actor = ?1 ORDER BY id",
+            COLUMNS
+        ),
+        params![actor],
+    )?;
+    discard(conn, actor, None)
+}
+
+/// Drops `actor`'s queued events, or only those for one item.
+pub fn discard(conn: &Connection, actor: &str, uid: Option<&str>) -> Result<()> {
+    conn.execute(
+        "DELETE FROM pending_events WHERE actor = ?1 AND (?2 IS NULL OR uid = ?2)",
+        params![actor, uid],
+    )?;
+    Ok(())
+}
+
+fn write(
+    conn: &Connection,
+    table: &str,
+    owner: &str,
+    actor: &str,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> Result<()> {
+    let at = get_current_date_time();
+    let uids: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for uid in uids {
+        let (list_name, item, values): (&String, &DumpItem, Vec<Values>) =
+            match (before.get(uid), after.get(uid)) {
+                (None, Some((list, new))) => (
+                    list,
+                    new,
+                    vec![("create", None, None, Some(new.title.clone()))],
+                ),
+                (Some((list, old)), None) => {
+                    let kind = if old.trash.is_some() {
+                        "purge"
+                    } else {
+                        "delete"
+                    };
+                    (list, old, vec![(kind, None, Some(old.title.clone()), None)])
+                }
+                (Some((old_list, old)), Some((list, new))) => {
+                    let mut values = Vec::new();
+                    if old_list != list {
+                        values.push(("move", None, Some(old_list.clone()), Some(list.clone())));
+                    }
+                    values.extend(edits(old, new));
+                    match (old.completed, new.completed) {
+                        (false, true) => values.push(("complete", None, None, None)),
+                        (true, false) => values.push(("incomplete", None, None, None)),
+                        _ => {}
+                    }
+                    match (&old.trash, &new.trash) {
+                        (None, Some(_)) => {
+                            values.push(("delete", None, Some(old.title.clone()), None))
+                        }
+                        (Some(_), None) => {
+                            values.push(("restore", None, None, Some(new.title.clone())))
+                        }
+                        _ => {}
+                    }
+                    match (&old.archived_at, &new.archived_at) {
+                        (None, Some(_)) => values.push(("archive", None, None, None)),
+                        (Some(_), None) => values.push(("unarchive", None, None, None)),
+                        _ => {}
+                    }
+                    (list, new, values)
+                }
+                (None, None) => continue,
+            };
+        for (kind, field, old_value, new_value) in values {
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    table, COLUMNS
+                ),
+                params![
+                    uid, owner, list_name, item.item, actor, at, kind, field, old_value, new_value
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The uid of the item most recently recorded under this list and number,
+/// which still finds items that have since been deleted.
+pub fn find_uid(
+    conn: &Connection,
+    owner: &str,
+    list_name: &str,
+    item_number: usize,
+) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT uid FROM todo_events WHERE owner = ?1 AND list_name = ?2 AND item_number = ?3
+        ORDER BY id DESC LIMIT 1",
+        params![owner, list_name, item_number],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// An item's events, oldest first, followed by the ones `actor` hasn't
+/// pushed yet.
+pub fn timeline(conn: &Connection, uid: &str, actor: &str) -> Result<Vec<Event>> {
+    let mut stmt = conn.prepare(
+        "SELECT actor, at, kind, field, old_value, new_value, 1 AS pushed, id
+        FROM todo_events WHERE uid = ?1
+        UNION ALL
+        SELECT actor, at, kind, field, old_value, new_value, 0, id
+        FROM pending_events WHERE uid = ?1 AND actor = ?2
+        ORDER BY pushed DESC, id",
+    )?;
+    let events = stmt.query_map(params![uid, actor], |row| {
+        Ok(Event {
+            actor: row.get(0)?,
+            at: row.get(1)?,
+            kind: row.get(2)?,
+            field: row.get(3)?,
+            old_value: row.get(4)?,
+            new_value: row.get(5)?,
+            pushed: row.get(6)?,
+        })
+    })?;
+    events.collect()
+}
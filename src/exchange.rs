@@ -0,0 +1,121 @@
+use crate::checklist;
+use crate::ics;
+use crate::taskwarrior;
+use crate::todo::{TodoItem, User};
+use crate::todotxt;
+use clap::ValueEnum;
+use rusqlite::{params, Connection, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// File formats understood by `import` and `export`.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExchangeFormat {
+    Todotxt,
+    Markdown,
+    Ics,
+    Taskwarrior,
+}
+
+impl ExchangeFormat {
+    /// Guesses the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "txt" => Some(ExchangeFormat::Todotxt),
+            "md" | "markdown" => Some(ExchangeFormat::Markdown),
+            "ics" => Some(ExchangeFormat::Ics),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Existing items that were matched and overwritten.
+    pub updated: usize,
+    /// Line number and reason for each entry that was skipped.
+    pub errors: Vec<(usize, String)>,
+}
+
+/// Uids of items stored under other users, from [`foreign_uids`].
+pub type Foreign = HashSet<String>;
+
+/// The uids stored under users other than `user_name`.
+pub fn foreign_uids(conn: &Connection, user_name: &str) -> Result<Foreign> {
+    let mut stmt = conn.prepare(
+        "SELECT t.uid FROM todos t JOIN users u ON u.id = t.user_id
+        WHERE u.user_name != ?1 AND t.uid IS NOT NULL",
+    )?;
+    let uids = stmt.query_map(params![user_name], |row| row.get(0))?;
+    uids.collect()
+}
+
+/// Gives a new item the uid from the file, unless another user's item or
+/// one of the user's own items in the trash already has it; the item then
+/// keeps its fresh uid, so importing someone else's export can't take over
+/// their items.
+pub fn adopt_uid(user: &User, todo: &mut TodoItem, uid: &str, foreign: &Foreign) {
+    if !foreign.contains(uid) && !user.has_uid(uid) {
+        todo.uid = uid.to_string();
+    }
+}
+
+/// Imports a file. Entries carrying the uid of one of the user's items
+/// outside the trash update it; see [`adopt_uid`] for new ones.
+pub fn import(
+    user: &mut User,
+    format: ExchangeFormat,
+    text: &str,
+    default_list: &str,
+    foreign: &Foreign,
+) -> ImportReport {
+    match format {
+        ExchangeFormat::Todotxt => todotxt::import(user, text, default_list),
+        ExchangeFormat::Markdown => checklist::import(user, text, default_list),
+        ExchangeFormat::Ics => ics::import(user, text, default_list, foreign),
+        ExchangeFormat::Taskwarrior => taskwarrior::import(user, text, default_list, foreign),
+    }
+}
+
+/// One list, or every list in name order.
+fn export_lists<'a>(user: &'a User, list_name: Option<&'a str>) -> Result<Vec<&'a str>, String> {
+    Ok(match list_name {
+        Some(name) if user.todo_lists.contains_key(name) => vec![name],
+        Some(name) => return Err(format!("No list named {}.", name)),
+        None => {
+            // Skip lists whose items are all deleted.
+            let mut names: Vec<&str> = user
+                .todo_lists
+                .iter()
+                .filter(|(_, list)| list.todos.iter().any(|todo| !todo.is_deleted))
+                .map(|(name, _)| name.as_str())
+                .collect();
+            names.sort_by_key(|name| name.to_lowercase());
+            names
+        }
+    })
+}
+
+/// Exports one list, or every list in name order.
+pub fn export(
+    user: &User,
+    format: ExchangeFormat,
+    list_name: Option<&str>,
+) -> Result<String, String> {
+    let lists = export_lists(user, list_name)?;
+    Ok(match format {
+        ExchangeFormat::Todotxt => todotxt::export(user, &lists)?,
+        ExchangeFormat::Markdown => checklist::export(user, &lists),
+        ExchangeFormat::Ics => ics::export(user, &lists),
+        ExchangeFormat::Taskwarrior => taskwarrior::export(user, &lists),
+    })
+}
+
+/// What [`export`] leaves out because the format has no place for it.
+pub fn left_out(user: &User, format: ExchangeFormat, list_name: Option<&str>) -> Vec<String> {
+    match (format, export_lists(user, list_name)) {
+        (ExchangeFormat::Todotxt, Ok(lists)) => todotxt::left_out(user, &lists),
+        _ => Vec::new(),
+    }
+}
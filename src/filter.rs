@@ -0,0 +1,474 @@
+use crate::todo::TodoItem;
+use chrono::{Duration, Local, NaiveDate};
+use std::fmt;
+
+// Grammar, loosest binding first:
+//
+//   expr    := and ("or" and)*
+//   and     := unary ("and" unary)*
+//   unary   := "not" unary | primary
+//   primary := "(" expr ")" | "done" | "open" | field (":" | op) value | word
+//
+// A bare word matches items whose title contains it.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    List,
+    Title,
+    Notes,
+    Item,
+    Created,
+    Completed,
+    Due,
+    Tag,
+    Priority,
+    Assignee,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("list", Field::List),
+    ("title", Field::Title),
+    ("notes", Field::Notes),
+    ("item", Field::Item),
+    ("created", Field::Created),
+    ("completed", Field::Completed),
+    ("due", Field::Due),
+    ("tag", Field::Tag),
+    ("priority", Field::Priority),
+    ("assignee", Field::Assignee),
+];
+
+impl Field {
+    pub fn name(self) -> &'static str {
+        FIELDS
+            .iter()
+            .find(|(_, field)| *field == self)
+            .map_or("", |(name, _)| name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Number(usize),
+    Date(NaiveDate),
+    Priority(char),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Done,
+    Compare { field: Field, op: Op, value: Value },
+}
+
+#[derive(Debug)]
+pub struct FilterError {
+    pub message: String,
+    /// Character offset into the expression where the problem was found.
+    pub position: usize,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.position + 1)
+    }
+}
+
+impl Filter {
+    pub fn list(name: &str) -> Self {
+        Filter::Compare {
+            field: Field::List,
+            op: Op::Eq,
+            value: Value::Text(name.to_string()),
+        }
+    }
+
+    pub fn assignee(user_name: &str) -> Self {
+        Filter::Compare {
+            field: Field::Assignee,
+            op: Op::Eq,
+            value: Value::Text(user_name.to_string()),
+        }
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn matches(&self, list_name: &str, todo: &TodoItem) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(list_name, todo) && b.matches(list_name, todo),
+            Filter::Or(a, b) => a.matches(list_name, todo) || b.matches(list_name, todo),
+            Filter::Not(a) => !a.matches(list_name, todo),
+            Filter::Done => todo.is_completed,
+            Filter::Compare { field, op, value } => match (field, value) {
+                (Field::List, Value::Text(name)) => {
+                    (*op == Op::Eq) == list_name.eq_ignore_ascii_case(name)
+                }
+                (Field::Title, Value::Text(text)) => (*op == Op::Eq) == contains(&todo.title, text),
+                (Field::Notes, Value::Text(text)) => (*op == Op::Eq) == contains(&todo.notes, text),
+                (Field::Item, Value::Number(n)) => compare(op, &todo.item_number, n),
+                (Field::Created, Value::Date(date)) => {
+                    date_of(&todo.datetime).is_some_and(|created| compare(op, &created, date))
+                }
+                (Field::Completed, Value::Date(date)) => todo
+                    .completed_at
+                    .as_deref()
+                    .and_then(date_of)
+                    .is_some_and(|completed| compare(op, &completed, date)),
+                (Field::Due, Value::Date(date)) => {
+                    todo.due_date().is_some_and(|due| compare(op, &due, date))
+                }
+                (Field::Tag, Value::Text(tag)) => {
+                    (*op == Op::Eq) == todo.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+                }
+                (Field::Assignee, Value::Text(name)) => {
+                    (*op == Op::Eq)
+                        == todo
+                            .assignee
+                            .as_ref()
+                            .is_some_and(|assignee| assignee.eq_ignore_ascii_case(name))
+                }
+                (Field::Priority, Value::Priority(p)) => {
+                    compare(op, &importance(todo.priority), &importance(Some(*p)))
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+fn date_of(datetime: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(datetime.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// `A` is the most important priority and no priority the least.
+fn importance(priority: Option<char>) -> u8 {
+    priority.map_or(0, |p| b'Z' + 1 - p as u8)
+}
+
+/// Parses a priority letter `A`-`Z`, or `high`, `medium` or `low` for A-C.
+pub fn parse_priority(raw: &str) -> Option<char> {
+    match raw.to_lowercase().as_str() {
+        "high" => Some('A'),
+        "medium" => Some('B'),
+        "low" => Some('C'),
+        _ => {
+            let mut chars = raw.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphabetic() => Some(c.to_ascii_uppercase()),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn compare<T: PartialOrd>(op: &Op, left: &T, right: &T) -> bool {
+    match op {
+        Op::Eq => left == right,
+        Op::Ne => left != right,
+        Op::Lt => left < right,
+        Op::Le => left <= right,
+        Op::Gt => left > right,
+        Op::Ge => left >= right,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Colon,
+    Op(Op),
+    Word(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let token = match c {
+            '(' => {
+                chars.next();
+                Token::Open
+            }
+            ')' => {
+                chars.next();
+                Token::Close
+            }
+            ':' => {
+                chars.next();
+                Token::Colon
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let equals = chars.next_if(|&(_, c)| c == '=').is_some();
+                match (c, equals) {
+                    ('=', _) => Token::Op(Op::Eq),
+                    ('!', true) => Token::Op(Op::Ne),
+                    ('<', false) => Token::Op(Op::Lt),
+                    ('<', true) => Token::Op(Op::Le),
+                    ('>', false) => Token::Op(Op::Gt),
+                    ('>', true) => Token::Op(Op::Ge),
+                    _ => {
+                        return Err(FilterError {
+                            message: "expected '!='".to_string(),
+                            position: start,
+                        })
+                    }
+                }
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, ch)) => word.push(ch),
+                        None => {
+                            return Err(FilterError {
+                                message: "unterminated quote".to_string(),
+                                position: start,
+                            })
+                        }
+                    }
+                }
+                Token::Word(word)
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some((_, ch)) =
+                    chars.next_if(|&(_, ch)| !ch.is_whitespace() && !"():=!<>\"'".contains(ch))
+                {
+                    word.push(ch);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(at, _)| *at)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, FilterError> {
+        Err(FilterError {
+            message,
+            position: self.offset(),
+        })
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Filter, FilterError> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            left = Filter::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut left = self.unary()?;
+        while self.keyword("and") {
+            left = left.and(self.unary()?);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Filter, FilterError> {
+        if self.keyword("not") {
+            Ok(Filter::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Filter, FilterError> {
+        match self.peek().cloned() {
+            Some(Token::Open) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                if self.peek() != Some(&Token::Close) {
+                    return self.error("expected ')'".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(Token::Word(word)) => {
+                let start = self.offset();
+                self.pos += 1;
+                let op = match self.peek() {
+                    Some(Token::Colon) => Op::Eq,
+                    Some(Token::Op(op)) => *op,
+                    _ => return self.bare_word(word, start),
+                };
+                self.pos += 1;
+                let Some(&(_, field)) = FIELDS
+                    .iter()
+                    .find(|(name, _)| word.eq_ignore_ascii_case(name))
+                else {
+                    let names: Vec<&str> = FIELDS.iter().map(|(name, _)| *name).collect();
+                    return Err(FilterError {
+                        message: format!(
+                            "unknown field '{}', expected one of: {}",
+                            word,
+                            names.join(", ")
+                        ),
+                        position: start,
+                    });
+                };
+                let Some(Token::Word(raw)) = self.peek().cloned() else {
+                    return self.error(format!("expected a value after '{}'", word));
+                };
+                let value = self.value(field, op, &raw)?;
+                self.pos += 1;
+                Ok(Filter::Compare { field, op, value })
+            }
+            Some(Token::Close) => self.error("unexpected ')'".to_string()),
+            Some(_) => self.error("expected a field name before the operator".to_string()),
+            None => self.error("unexpected end of filter".to_string()),
+        }
+    }
+
+    fn bare_word(&self, word: String, start: usize) -> Result<Filter, FilterError> {
+        match word.to_lowercase().as_str() {
+            "done" => Ok(Filter::Done),
+            "open" => Ok(Filter::Not(Box::new(Filter::Done))),
+            "and" | "or" => Err(FilterError {
+                message: format!("missing term before '{}'", word),
+                position: start,
+            }),
+            _ => Ok(Filter::Compare {
+                field: Field::Title,
+                op: Op::Eq,
+                value: Value::Text(word),
+            }),
+        }
+    }
+
+    fn value(&self, field: Field, op: Op, raw: &str) -> Result<Value, FilterError> {
+        match field {
+            Field::List | Field::Title | Field::Notes | Field::Tag | Field::Assignee => {
+                if op != Op::Eq && op != Op::Ne {
+                    return self.error(format!("'{}' only supports ':', '=' and '!='", field.name()));
+                }
+                Ok(Value::Text(raw.to_string()))
+            }
+            Field::Item => match raw.parse() {
+                Ok(n) => Ok(Value::Number(n)),
+                Err(_) => self.error(format!("'{}' is not an item number", raw)),
+            },
+            Field::Priority => match parse_priority(raw) {
+                Some(p) => Ok(Value::Priority(p)),
+                None => self.error(format!(
+                    "'{}' is not a priority; use A-Z, high, medium or low",
+                    raw
+                )),
+            },
+            Field::Created | Field::Completed | Field::Due => match parse_date(raw) {
+                Some(date) => Ok(Value::Date(date)),
+                None => self.error(format!(
+                    "'{}' is not a date; use YYYY-MM-DD, today, yesterday, tomorrow or an offset like -7d or +2w",
+                    raw
+                )),
+            },
+        }
+    }
+}
+
+/// Parses `YYYY-MM-DD`, `today`, `yesterday`, `tomorrow` or a day/week offset
+/// from today such as `-7d` or `+2w`.
+pub fn parse_date(raw: &str) -> Option<NaiveDate> {
+    let today = Local::now().date_naive();
+    match raw.to_lowercase().as_str() {
+        "today" => return Some(today),
+        "yesterday" => return Some(today - Duration::days(1)),
+        "tomorrow" => return Some(today + Duration::days(1)),
+        _ => {}
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return Some(date);
+    }
+    let (sign, rest) = match raw.chars().next()? {
+        '+' => (1, &raw[1..]),
+        '-' => (-1, &raw[1..]),
+        _ => return None,
+    };
+    let (split, _) = rest.char_indices().last()?;
+    let (amount, unit) = rest.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    let days = match unit {
+        "d" => amount,
+        "w" => amount.checked_mul(7)?,
+        _ => return None,
+    };
+    today.checked_add_signed(Duration::try_days(days.checked_mul(sign)?)?)
+}
+
+/// Parses a due date as [`parse_date`] does, giving it back as `YYYY-MM-DD`.
+pub fn parse_due(raw: &str) -> Result<String, String> {
+    parse_date(raw)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .ok_or_else(|| {
+            format!(
+                "'{}' is not a date; use YYYY-MM-DD, today, tomorrow or an offset like +3d or +2w",
+                raw
+            )
+        })
+}
+
+pub fn parse(input: &str) -> Result<Filter, FilterError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        end: input.chars().count(),
+    };
+    let filter = parser.expr()?;
+    if parser.pos < parser.tokens.len() {
+        return parser.error("expected 'and', 'or' or the end of the filter".to_string());
+    }
+    Ok(filter)
+}
@@ -0,0 +1,99 @@
+//! Pomodoro focus sessions for `todo focus`: a work countdown, then a break,
+//! ringing the terminal bell at the end of each. Every finished work period
+//! is logged as a pomodoro time entry on the item.
+//!
+//! Ctrl-C stops a session early. The handler is installed once for the whole
+//! program, so outside a session Ctrl-C still exits as usual.
+
+use crate::timesheet;
+use crate::todo::TodoItem;
+use rusqlite::{Connection, Result};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static FOCUSING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub fn install_interrupt_handler() -> std::result::Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if FOCUSING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    })
+}
+
+/// Shows `label` with the time left, once a second, until `seconds` have
+/// passed or Ctrl-C is pressed. Returns how many seconds went by, or 0 for
+/// a time too far off to count down to.
+fn countdown(label: &str, seconds: u64) -> u64 {
+    let start = Instant::now();
+    let Some(end) = start.checked_add(Duration::from_secs(seconds)) else {
+        return 0;
+    };
+    let mut shown = None;
+    loop {
+        let now = Instant::now();
+        if now >= end || INTERRUPTED.load(Ordering::SeqCst) {
+            break;
+        }
+        let left = (end - now).as_secs_f64().ceil() as u64;
+        if shown != Some(left) {
+            print!("\r{} {:02}:{:02} ", label, left / 60, left % 60);
+            io::stdout().flush().ok();
+            shown = Some(left);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    println!();
+    start.elapsed().as_secs().min(seconds)
+}
+
+fn bell() {
+    print!("\x07");
+    io::stdout().flush().ok();
+}
+
+/// Runs `rounds` pomodoros of `work` seconds, each followed by a break of
+/// `rest` seconds except the last. Returns how many pomodoros were finished;
+/// time worked in one cut short by Ctrl-C is logged as ordinary time.
+pub fn run(
+    conn: &Connection,
+    user_name: &str,
+    list_name: &str,
+    todo: &TodoItem,
+    work: u64,
+    rest: u64,
+    rounds: usize,
+) -> Result<usize> {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    FOCUSING.store(true, Ordering::SeqCst);
+    let mut finished = 0;
+    let result = (|| {
+        for round in 1..=rounds {
+            let worked = countdown(&format!("Pomodoro {}/{}: focus", round, rounds), work);
+            if worked < work {
+                if worked > 0 {
+                    timesheet::log(conn, user_name, list_name, todo, worked as i64, false)?;
+                }
+                break;
+            }
+            timesheet::log(conn, user_name, list_name, todo, work as i64, true)?;
+            finished += 1;
+            bell();
+            if round == rounds {
+                break;
+            }
+            if countdown("Break", rest) < rest {
+                break;
+            }
+            bell();
+        }
+        Ok(())
+    })();
+    FOCUSING.store(false, Ordering::SeqCst);
+    result.map(|()| finished)
+}
@@ -0,0 +1,415 @@
+//! iCalendar (RFC 5545) `VTODO` components.
+//!
+//! Items are matched by `UID` on import, so re-importing a calendar updates
+//! the items it created instead of duplicating them. `CATEGORIES` holds the
+//! list name followed by the item's tags, `PRIORITY` 1-9 maps to A-I and
+//! `DUE` is the due date. A completed `VTODO` without `COMPLETED` counts as
+//! completed at its `LAST-MODIFIED` time, or else when it was imported.
+//! Other properties (`RRULE`, `LAST-MODIFIED`, ...) and nested components
+//! such as `VALARM` are kept verbatim in the item's `extra` map under
+//! [`EXTRA_KEY`] and written back on export.
+
+use crate::exchange::{adopt_uid, Foreign, ImportReport};
+use crate::todo::{get_current_date_time, TodoItem, User};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+
+const DATE_TIME: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The `extra` entry holding the content lines items have no field for.
+pub const EXTRA_KEY: &str = "ics";
+
+/// Properties that map to item fields, or that export writes itself.
+const KNOWN: [&str; 10] = [
+    "UID",
+    "DTSTAMP",
+    "CREATED",
+    "SUMMARY",
+    "STATUS",
+    "COMPLETED",
+    "DUE",
+    "PRIORITY",
+    "CATEGORIES",
+    "DESCRIPTION",
+];
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') | Some('N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            (c, false) => out.push(c),
+        }
+    }
+    out
+}
+
+/// Splits on `,` except where it is escaped as `\,`.
+fn split_list(value: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        if c == ',' && !escaped {
+            parts.push(String::new());
+            continue;
+        }
+        escaped = c == '\\' && !escaped;
+        parts.last_mut().unwrap().push(c);
+    }
+    parts
+        .iter()
+        .map(|part| unescape(part.trim()))
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Folds a content line to 75 octets per line, as RFC 5545 requires.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// Local `YYYY-MM-DD HH:MM:SS` to UTC `YYYYMMDDTHHMMSSZ`.
+pub fn to_utc(datetime: &str) -> Option<String> {
+    let naive = NaiveDateTime::parse_from_str(datetime, DATE_TIME).ok()?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some(
+        local
+            .with_timezone(&Utc)
+            .format("%Y%m%dT%H%M%SZ")
+            .to_string(),
+    )
+}
+
+/// UTC, floating or date-only iCalendar values to local `YYYY-MM-DD HH:MM:SS`.
+pub fn from_ical(value: &str) -> Option<String> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(
+            Utc.from_utc_datetime(&naive)
+                .with_timezone(&Local)
+                .format(DATE_TIME)
+                .to_string(),
+        );
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Some(naive.format(DATE_TIME).to_string());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    Some(format!("{} 00:00:00", date.format("%Y-%m-%d")))
+}
+
+/// A `DUE` value: a date for date-only values, otherwise a local date and time.
+fn due_from_ical(value: &str) -> Option<String> {
+    match NaiveDate::parse_from_str(value, "%Y%m%d") {
+        Ok(date) => Some(date.format("%Y-%m-%d").to_string()),
+        Err(_) => from_ical(value),
+    }
+}
+
+fn format_item(list_name: &str, todo: &TodoItem, stamp: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", todo.uid),
+        format!("DTSTAMP:{}", stamp),
+    ];
+    if let Some(created) = to_utc(&todo.datetime) {
+        lines.push(format!("CREATED:{}", created));
+    }
+    lines.push(format!("SUMMARY:{}", escape(&todo.title)));
+    if todo.is_completed {
+        lines.push("STATUS:COMPLETED".to_string());
+        if let Some(completed) = todo.completed_at.as_deref().and_then(to_utc) {
+            lines.push(format!("COMPLETED:{}", completed));
+        }
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    match todo.due.as_deref() {
+        Some(due) if due.len() == 10 => {
+            lines.push(format!("DUE;VALUE=DATE:{}", due.replace('-', "")))
+        }
+        Some(due) => lines.extend(to_utc(due).map(|due| format!("DUE:{}", due))),
+        None => {}
+    }
+    if let Some(priority) = todo.priority.filter(|p| *p <= 'I') {
+        lines.push(format!("PRIORITY:{}", priority as u8 - b'A' + 1));
+    }
+    let categories: Vec<String> = std::iter::once(list_name)
+        .chain(todo.tags.iter().map(String::as_str))
+        .map(escape)
+        .collect();
+    lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    if !todo.notes.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape(&todo.notes)));
+    }
+    if let Some(Value::Array(kept)) = todo.extra.get(EXTRA_KEY) {
+        // Items imported before due dates were read keep their DUE here.
+        let stale_due = |line: &&str| {
+            todo.due.is_some() && split_property(line).is_some_and(|(name, _)| name == "DUE")
+        };
+        lines.extend(
+            kept.iter()
+                .filter_map(Value::as_str)
+                .filter(|line| !stale_due(line))
+                .map(String::from),
+        );
+    }
+    lines.push("END:VTODO".to_string());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+pub fn export(user: &User, lists: &[&str]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out =
+        fold("BEGIN:VCALENDAR") + &fold("VERSION:2.0") + &fold("PRODID:-//cli-todo//todo//EN");
+    for list_name in lists {
+        for todo in user.todo_lists[*list_name]
+            .todos
+            .iter()
+            .filter(|t| !t.is_deleted)
+        {
+            out.push_str(&format_item(list_name, todo, &stamp));
+        }
+    }
+    out + &fold("END:VCALENDAR")
+}
+
+/// Properties of one `VTODO`, keyed by upper-cased name.
+#[derive(Default)]
+struct Component {
+    line: usize,
+    /// How many nested components (such as `VALARM`) are open.
+    depth: usize,
+    properties: Vec<(String, String)>,
+    /// Content lines of unknown properties and nested components, as read.
+    unknown: Vec<String>,
+}
+
+impl Component {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn date(&self, name: &str) -> Result<Option<String>, String> {
+        match self.get(name) {
+            Some(value) => from_ical(value)
+                .map(Some)
+                .ok_or_else(|| format!("invalid {} '{}'", name, value)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Unfolds continuation lines, keeping the number of each line's first row.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Splits `NAME;PARAM=x:value` into the upper-cased name and the value,
+/// skipping colons inside quoted parameter values.
+fn split_property(line: &str) -> Option<(String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let name = line[..colon].split(';').next()?.to_uppercase();
+    Some((name, &line[colon + 1..]))
+}
+
+fn apply(todo: &mut TodoItem, component: &Component, tags: Vec<String>) -> Result<(), String> {
+    let title = component.get("SUMMARY").map(unescape).unwrap_or_default();
+    if title.trim().is_empty() {
+        return Err("VTODO has no SUMMARY".to_string());
+    }
+    let created = component.date("CREATED")?;
+    let completed_at = component.date("COMPLETED")?;
+    let is_completed = component.get("STATUS") == Some("COMPLETED") || completed_at.is_some();
+    let completed_at = match completed_at {
+        None if is_completed => Some(
+            todo.completed_at
+                .clone()
+                .filter(|_| todo.is_completed)
+                .or(component.date("LAST-MODIFIED")?)
+                .unwrap_or_else(get_current_date_time),
+        ),
+        completed_at => completed_at,
+    };
+    let due = match component.get("DUE") {
+        Some(value) => {
+            Some(due_from_ical(value).ok_or_else(|| format!("invalid DUE '{}'", value))?)
+        }
+        None => None,
+    };
+    let priority = match component.get("PRIORITY").map(str::trim) {
+        None | Some("0") => None,
+        Some(value) => match value.parse::<u8>() {
+            Ok(n @ 1..=9) => Some((b'A' + n - 1) as char),
+            _ => return Err(format!("invalid PRIORITY '{}'", value)),
+        },
+    };
+    if let Some(created) = created {
+        todo.datetime = created;
+    }
+    todo.title = title;
+    todo.is_completed = is_completed;
+    todo.completed_at = completed_at;
+    todo.due = due;
+    todo.priority = priority;
+    todo.tags = tags;
+    todo.notes = component
+        .get("DESCRIPTION")
+        .map(unescape)
+        .unwrap_or_default();
+    if component.unknown.is_empty() {
+        todo.extra.remove(EXTRA_KEY);
+    } else {
+        todo.extra.insert(
+            EXTRA_KEY.to_string(),
+            Value::from(component.unknown.clone()),
+        );
+    }
+    todo.is_modified = true;
+    Ok(())
+}
+
+fn import_component(
+    user: &mut User,
+    component: &Component,
+    default_list: &str,
+    foreign: &Foreign,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let mut categories = component
+        .get("CATEGORIES")
+        .map(split_list)
+        .unwrap_or_default();
+    let list_name = if categories.is_empty() {
+        default_list.to_string()
+    } else {
+        categories.remove(0)
+    };
+    let uid = component
+        .get("UID")
+        .map(str::trim)
+        .filter(|uid| !uid.is_empty());
+    if let Some(todo) = uid.and_then(|uid| user.find_live(uid)) {
+        apply(todo, component, categories)?;
+        report.updated += 1;
+        return Ok(());
+    }
+    let mut todo = TodoItem::new(0, String::new());
+    if let Some(uid) = uid {
+        adopt_uid(user, &mut todo, uid, foreign);
+    }
+    apply(&mut todo, component, categories)?;
+    user.get_or_add_todo_list(&list_name).add_item(todo);
+    report.imported += 1;
+    Ok(())
+}
+
+/// Creates or updates an item for each `VTODO`; other top-level components
+/// are ignored.
+pub fn import(user: &mut User, text: &str, default_list: &str, foreign: &Foreign) -> ImportReport {
+    let mut report = ImportReport::default();
+    let mut current: Option<Component> = None;
+    for (line_number, line) in unfold(text) {
+        let Some((name, value)) = split_property(&line) else {
+            continue;
+        };
+        match (
+            name.as_str(),
+            value.trim().to_uppercase().as_str(),
+            &mut current,
+        ) {
+            ("BEGIN", "VTODO", _) => {
+                current = Some(Component {
+                    line: line_number,
+                    ..Default::default()
+                })
+            }
+            ("END", "VTODO", Some(component)) if component.depth == 0 => {
+                let component = current.take().unwrap();
+                if let Err(e) =
+                    import_component(user, &component, default_list, foreign, &mut report)
+                {
+                    report.errors.push((component.line, e));
+                }
+            }
+            ("BEGIN", _, Some(component)) => {
+                component.depth += 1;
+                component.unknown.push(line);
+            }
+            ("END", _, Some(component)) if component.depth > 0 => {
+                component.depth -= 1;
+                component.unknown.push(line);
+            }
+            (_, _, Some(component)) if component.depth > 0 => component.unknown.push(line),
+            (_, _, Some(component)) if !KNOWN.contains(&name.as_str()) => {
+                // Kept for export, but also read for completions without COMPLETED.
+                if name == "LAST-MODIFIED" {
+                    component.properties.push((name, value.to_string()));
+                }
+                component.unknown.push(line)
+            }
+            (_, _, Some(component)) => {
+                if name == "CATEGORIES" {
+                    if let Some((_, existing)) = component
+                        .properties
+                        .iter_mut()
+                        .find(|(key, _)| key == "CATEGORIES")
+                    {
+                        existing.push(',');
+                        existing.push_str(value);
+                        continue;
+                    }
+                }
+                component.properties.push((name, value.to_string()));
+            }
+            _ => {}
+        }
+    }
+    if let Some(component) = current {
+        report
+            .errors
+            .push((component.line, "VTODO is missing END:VTODO".to_string()));
+    }
+    report
+}
@@ -0,0 +1,291 @@
+//! Undo and redo.
+//!
+//! Each command that changes items is stored as an operation holding every
+//! item it touched, before and after. Undoing puts the `before` states back
+//! and redoing the `after` ones, so any command can be reverted without
+//! knowing what it did. Operations are written to the `journal` table as they
+//! happen, so history outlives the session. An operation is only undone or
+//! redone while its items are still as it left them, so a pull or a fresh
+//! login in between can't bring back stale states.
+
+use crate::comments;
+use crate::dump::DumpItem;
+use crate::todo::{get_current_date_time, TodoItem, User};
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Every item by uid, with the list it is in, including those in the trash.
+pub type Snapshot = HashMap<String, (String, DumpItem)>;
+
+/// Whether a snapshot holds the item: deleted items only while in the trash.
+fn kept(todo: &TodoItem) -> bool {
+    !todo.is_deleted || todo.trash.is_some()
+}
+
+/// The items of one list, or of every list when `list_name` is `None`.
+pub fn snapshot(user: &User, list_name: Option<&str>) -> Snapshot {
+    user.todo_lists
+        .iter()
+        .filter(|(name, _)| list_name.is_none_or(|list_name| list_name == name.as_str()))
+        .flat_map(|(list_name, list)| {
+            list.todos
+                .iter()
+                .filter(|todo| kept(todo))
+                .map(move |todo| {
+                    (
+                        todo.uid.clone(),
+                        (list_name.clone(), DumpItem::from_item(todo)),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// One item's state either side of an operation; `None` when it didn't exist.
+#[derive(Serialize, Deserialize)]
+pub struct Change {
+    pub list: String,
+    pub uid: String,
+    pub before: Option<DumpItem>,
+    pub after: Option<DumpItem>,
+}
+
+pub struct Operation {
+    pub id: i64,
+    pub at: String,
+    pub command: String,
+    pub changes: Vec<Change>,
+    pub undone: bool,
+}
+
+fn diff(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let uids: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut changes: Vec<Change> = uids
+        .into_iter()
+        .filter_map(|uid| {
+            let old = before.get(uid);
+            let new = after.get(uid);
+            if old.map(|(_, item)| item) == new.map(|(_, item)| item) {
+                return None;
+            }
+            Some(Change {
+                list: old.or(new).map(|(list, _)| list.clone())?,
+                uid: uid.clone(),
+                before: old.map(|(_, item)| item.clone()),
+                after: new.map(|(_, item)| item.clone()),
+            })
+        })
+        .collect();
+    changes.sort_by_key(|change| {
+        let item = change.before.as_ref().or(change.after.as_ref());
+        (change.list.clone(), item.map(|item| item.item))
+    });
+    changes
+}
+
+/// Puts an item into `state`, re-adding it if it is gone.
+fn apply(user: &mut User, list_name: &str, uid: &str, state: Option<&DumpItem>) {
+    if let Some(todo) = user.find_by_uid(uid) {
+        match state {
+            Some(state) => state.apply(todo),
+            None => {
+                todo.trash = None;
+                todo.is_deleted = true;
+            }
+        }
+        return;
+    }
+    if let Some(state) = state {
+        let list = user.get_or_add_todo_list(list_name);
+        if list.todos.iter().any(|todo| todo.item_number == state.item) {
+            list.add_item(state.to_item());
+        } else {
+            list.todos.push(state.to_item());
+        }
+    }
+}
+
+/// An item's state as a snapshot would hold it.
+fn current(user: &User, uid: &str) -> Option<DumpItem> {
+    user.todo_lists
+        .values()
+        .flat_map(|list| list.todos.iter())
+        .find(|todo| todo.uid == uid && kept(todo))
+        .map(DumpItem::from_item)
+}
+
+/// Why an operation can't be undone (or redone): one of its items isn't in
+/// the state the operation left it in (or found it in), or undoing would
+/// drop a comment that has already been pushed.
+fn blocked(
+    conn: &Connection,
+    user: &User,
+    operation: &Operation,
+    undo: bool,
+) -> Result<Option<String>> {
+    for change in &operation.changes {
+        let (expected, target) = match undo {
+            true => (&change.after, &change.before),
+            false => (&change.before, &change.after),
+        };
+        let item = expected
+            .as_ref()
+            .or(target.as_ref())
+            .map_or(0, |item| item.item);
+        if current(user, &change.uid) != *expected {
+            return Ok(Some(format!(
+                "item {} of {} has changed since, or hasn't been pulled",
+                item, change.list
+            )));
+        }
+        let Some(expected) = expected else {
+            continue;
+        };
+        for comment in &expected.comments {
+            let dropped = target
+                .as_ref()
+                .is_none_or(|target| !target.comments.contains(comment));
+            if dropped && comments::exists(conn, &comment.uid)? {
+                return Ok(Some(format!(
+                    "a comment on item {} of {} has been pushed",
+                    item, change.list
+                )));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// What [`Journal::undo`] or [`Journal::redo`] did.
+#[derive(Debug, Default, PartialEq)]
+pub struct Replayed {
+    /// The commands reverted or re-applied, in order.
+    pub commands: Vec<String>,
+    /// The command it stopped at and why, if it stopped early.
+    pub refused: Option<(String, String)>,
+}
+
+pub struct Journal {
+    user_name: String,
+    /// Oldest first; the undone ones always come last.
+    pub operations: Vec<Operation>,
+}
+
+impl Journal {
+    pub fn load(conn: &Connection, user_name: &str) -> Result<Self> {
+        let mut stmt = conn.prepare(
+            "SELECT id, at, command, changes, undone FROM journal
+            WHERE user_name = ?1 ORDER BY id",
+        )?;
+        let operations = stmt
+            .query_map(params![user_name], |row| {
+                Ok(Operation {
+                    id: row.get(0)?,
+                    at: row.get(1)?,
+                    command: row.get(2)?,
+                    changes: serde_json::from_str(&row.get::<_, String>(3)?).unwrap_or_default(),
+                    undone: row.get::<_, i32>(4)? != 0,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Journal {
+            user_name: user_name.to_string(),
+            operations,
+        })
+    }
+
+    /// Records what `command` changed between two snapshots. Returns whether
+    /// there was anything to record; a new operation discards the redo history.
+    pub fn record(
+        &mut self,
+        conn: &Connection,
+        command: &str,
+        before: &Snapshot,
+        after: &Snapshot,
+    ) -> Result<bool> {
+        let changes = diff(before, after);
+        if changes.is_empty() {
+            return Ok(false);
+        }
+        conn.execute(
+            "DELETE FROM journal WHERE user_name = ?1 AND undone = 1",
+            params![self.user_name],
+        )?;
+        self.operations.retain(|operation| !operation.undone);
+        let at = get_current_date_time();
+        conn.execute(
+            "INSERT INTO journal (user_name, at, command, changes) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.user_name,
+                at,
+                command,
+                serde_json::to_string(&changes).unwrap()
+            ],
+        )?;
+        self.operations.push(Operation {
+            id: conn.last_insert_rowid(),
+            at,
+            command: command.to_string(),
+            changes,
+            undone: false,
+        });
+        Ok(true)
+    }
+
+    fn mark(conn: &Connection, operation: &mut Operation, undone: bool) -> Result<()> {
+        conn.execute(
+            "UPDATE journal SET undone = ?2 WHERE id = ?1",
+            params![operation.id, undone as i32],
+        )?;
+        operation.undone = undone;
+        Ok(())
+    }
+
+    /// Reverts the latest `count` operations, newest first, stopping at the
+    /// first one that can't be.
+    pub fn undo(&mut self, conn: &Connection, user: &mut User, count: usize) -> Result<Replayed> {
+        let mut undone = Replayed::default();
+        for operation in self
+            .operations
+            .iter_mut()
+            .rev()
+            .filter(|operation| !operation.undone)
+            .take(count)
+        {
+            if let Some(reason) = blocked(conn, user, operation, true)? {
+                undone.refused = Some((operation.command.clone(), reason));
+                break;
+            }
+            for change in operation.changes.iter().rev() {
+                apply(user, &change.list, &change.uid, change.before.as_ref());
+            }
+            Journal::mark(conn, operation, true)?;
+            undone.commands.push(operation.command.clone());
+        }
+        Ok(undone)
+    }
+
+    /// Re-applies the `count` most recently undone operations, oldest first,
+    /// stopping at the first one that can't be.
+    pub fn redo(&mut self, conn: &Connection, user: &mut User, count: usize) -> Result<Replayed> {
+        let mut redone = Replayed::default();
+        for operation in self
+            .operations
+            .iter_mut()
+            .filter(|operation| operation.undone)
+            .take(count)
+        {
+            if let Some(reason) = blocked(conn, user, operation, false)? {
+                redone.refused = Some((operation.command.clone(), reason));
+                break;
+            }
+            for change in &operation.changes {
+                apply(user, &change.list, &change.uid, change.after.as_ref());
+            }
+            Journal::mark(conn, operation, false)?;
+            redone.commands.push(operation.command.clone());
+        }
+        Ok(redone)
+    }
+}
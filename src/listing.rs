@@ -0,0 +1,253 @@
+use crate::filter::Filter;
+use crate::todo::{TodoItem, User};
+use chrono::Local;
+use clap::ValueEnum;
+use std::cmp::Ordering;
+
+/// An item together with the list it belongs to.
+#[derive(Clone, Copy)]
+pub struct Row<'a> {
+    pub list_name: &'a str,
+    pub todo: &'a TodoItem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    List,
+    Item,
+    Title,
+    Created,
+    Status,
+    Priority,
+    CompletedAt,
+    Due,
+}
+
+const SORT_FIELDS: &[(&str, SortField)] = &[
+    ("list", SortField::List),
+    ("item", SortField::Item),
+    ("title", SortField::Title),
+    ("created", SortField::Created),
+    ("status", SortField::Status),
+    ("priority", SortField::Priority),
+    ("completed-at", SortField::CompletedAt),
+    ("due", SortField::Due),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey<F = SortField> {
+    pub field: F,
+    pub descending: bool,
+}
+
+/// Parses `field`, `field:asc` or `field:desc`.
+pub fn parse_sort_key(raw: &str) -> Result<SortKey, String> {
+    parse_key(raw, SORT_FIELDS)
+}
+
+/// [`parse_sort_key`] with other fields, for things that aren't items.
+pub fn parse_key<F: Copy>(raw: &str, fields: &[(&str, F)]) -> Result<SortKey<F>, String> {
+    let (name, direction) = raw.split_once(':').unwrap_or((raw, "asc"));
+    let descending = match direction {
+        "asc" => false,
+        "desc" => true,
+        _ => {
+            return Err(format!(
+                "unknown direction '{}', expected asc or desc",
+                direction
+            ))
+        }
+    };
+    match fields.iter().find(|(key, _)| *key == name) {
+        Some(&(_, field)) => Ok(SortKey { field, descending }),
+        None => {
+            let names: Vec<&str> = fields.iter().map(|(key, _)| *key).collect();
+            Err(format!(
+                "unknown sort key '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    List,
+    Status,
+    /// One group per tag, so an item with several tags shows up in each.
+    Tag,
+    /// Overdue, today, the next 7 days, later, and no due date.
+    Due,
+    None,
+}
+
+/// The `--group-by due` groups, in the order they are shown.
+const DUE_GROUPS: &[&str] = &[
+    "Overdue",
+    "Earlier",
+    "Today",
+    "Next 7 days",
+    "Later",
+    "No due date",
+];
+
+/// Which due date group an item falls in. Finished items are never overdue;
+/// those due before today are `Earlier`.
+pub fn due_group(todo: &TodoItem) -> &'static str {
+    let Some(due) = todo.due_date() else {
+        return "No due date";
+    };
+    if todo.is_overdue() {
+        return "Overdue";
+    }
+    let today = Local::now().date_naive();
+    match (due - today).num_days() {
+        ..0 => "Earlier",
+        0 => "Today",
+        1..=7 => "Next 7 days",
+        _ => "Later",
+    }
+}
+
+fn compare(a: &Row, b: &Row, field: SortField) -> Ordering {
+    match field {
+        SortField::List => a.list_name.to_lowercase().cmp(&b.list_name.to_lowercase()),
+        SortField::Item => a.todo.item_number.cmp(&b.todo.item_number),
+        SortField::Title => a
+            .todo
+            .title
+            .to_lowercase()
+            .cmp(&b.todo.title.to_lowercase()),
+        SortField::Created => a.todo.datetime.cmp(&b.todo.datetime),
+        SortField::Status => a.todo.is_completed.cmp(&b.todo.is_completed),
+        // Items without a value sort after those with one.
+        SortField::Priority => (a.todo.priority.is_none(), a.todo.priority)
+            .cmp(&(b.todo.priority.is_none(), b.todo.priority)),
+        SortField::CompletedAt => (a.todo.completed_at.is_none(), &a.todo.completed_at)
+            .cmp(&(b.todo.completed_at.is_none(), &b.todo.completed_at)),
+        SortField::Due => {
+            (a.todo.due.is_none(), &a.todo.due).cmp(&(b.todo.due.is_none(), &b.todo.due))
+        }
+    }
+}
+
+/// Collects the user's live items, or with `archived` their archived ones,
+/// that match `filter`, ordered by list name and then item number.
+pub fn rows<'a>(user: &'a User, filter: Option<&Filter>, archived: bool) -> Vec<Row<'a>> {
+    let mut rows: Vec<Row> = user
+        .todo_lists
+        .values()
+        .flat_map(|list| {
+            list.todos.iter().map(|todo| Row {
+                list_name: &list.title,
+                todo,
+            })
+        })
+        .filter(|row| !row.todo.is_deleted && row.todo.archived_at.is_some() == archived)
+        .filter(|row| filter.is_none_or(|f| f.matches(row.list_name, row.todo)))
+        .collect();
+    rows.sort_by(|a, b| {
+        compare(a, b, SortField::List)
+            .then_with(|| a.list_name.cmp(b.list_name))
+            .then_with(|| compare(a, b, SortField::Item))
+    });
+    rows
+}
+
+/// The user's open items that have a due date, soonest first.
+pub fn agenda(user: &User) -> Vec<Row<'_>> {
+    let mut rows: Vec<Row> = rows(user, None, false)
+        .into_iter()
+        .filter(|row| !row.todo.is_completed && row.todo.due.is_some())
+        .collect();
+    sort_rows(
+        &mut rows,
+        &[SortKey {
+            field: SortField::Due,
+            descending: false,
+        }],
+    );
+    rows
+}
+
+pub fn find<'a>(user: &'a User, list_name: &str, item_number: usize) -> Option<Row<'a>> {
+    let list = user.todo_lists.get(list_name)?;
+    let todo = list.get_item(item_number)?;
+    Some(Row {
+        list_name: &list.title,
+        todo,
+    })
+}
+
+/// Stable sort by each key in turn, so rows that tie keep the default order.
+pub fn sort_rows(rows: &mut [Row], keys: &[SortKey]) {
+    sort_by_row(rows, keys, |row| *row);
+}
+
+/// [`sort_rows`] for anything that has a row, such as a search hit.
+pub fn sort_by_row<'a, T>(items: &mut [T], keys: &[SortKey], row_of: impl Fn(&T) -> Row<'a>) {
+    items.sort_by(|a, b| compare_keys(&row_of(a), &row_of(b), keys, compare));
+}
+
+/// Compares by each key in turn with `compare`, until one tells them apart.
+pub fn compare_keys<T, F: Copy>(
+    a: &T,
+    b: &T,
+    keys: &[SortKey<F>],
+    compare: impl Fn(&T, &T, F) -> Ordering,
+) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = compare(a, b, key.field);
+            if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Splits sorted rows into titled groups, keeping row order within a group.
+/// Grouped by tag, untagged items go in a last group with an empty name.
+pub fn group_rows<'a>(rows: Vec<Row<'a>>, by: GroupBy) -> Vec<(String, Vec<Row<'a>>)> {
+    group_by_row(rows, by, |row| *row)
+}
+
+/// [`group_rows`] for anything that has a row, such as a search hit.
+pub fn group_by_row<'a, T: Clone>(
+    items: Vec<T>,
+    by: GroupBy,
+    row_of: impl Fn(&T) -> Row<'a>,
+) -> Vec<(String, Vec<T>)> {
+    let mut groups: Vec<(String, Vec<T>)> = Vec::new();
+    let groups_of = |row: &Row| match by {
+        GroupBy::List => vec![row.list_name.to_string()],
+        GroupBy::Status if row.todo.is_completed => vec!["Done".to_string()],
+        GroupBy::Status => vec!["Open".to_string()],
+        GroupBy::Tag if row.todo.tags.is_empty() => vec![String::new()],
+        GroupBy::Tag => row.todo.tags.clone(),
+        GroupBy::Due => vec![due_group(row.todo).to_string()],
+        GroupBy::None => vec![String::new()],
+    };
+    for item in items {
+        for name in groups_of(&row_of(&item)) {
+            match groups.iter_mut().find(|(group, _)| *group == name) {
+                Some((_, members)) => members.push(item.clone()),
+                None => groups.push((name, vec![item.clone()])),
+            }
+        }
+    }
+    match by {
+        GroupBy::List => groups.sort_by_key(|(name, _)| name.to_lowercase()),
+        GroupBy::Status => groups.sort_by_key(|(name, _)| name == "Done"),
+        GroupBy::Tag => groups.sort_by_key(|(name, _)| (name.is_empty(), name.to_lowercase())),
+        GroupBy::Due => {
+            groups.sort_by_key(|(name, _)| DUE_GROUPS.iter().position(|group| group == name))
+        }
+        GroupBy::None => {}
+    }
+    groups
+}
@@ -1,150 +1,1149 @@
+mod archive;
+mod backup;
+mod chart;
+mod checklist;
 mod cli;
+mod color;
+mod comments;
+mod config;
 mod db;
+mod dump;
+mod editor;
+mod events;
+mod exchange;
+mod filter;
+mod focus;
+mod ics;
+mod journal;
+mod listing;
+mod notifications;
+mod output;
+mod search;
+mod sharing;
+mod stats;
+mod table;
+mod taskwarrior;
 mod tests;
+mod timesheet;
 mod todo;
+mod todotxt;
+mod trash;
 
+use chrono::{Datelike, Duration, Local};
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{split_args, BackupAction, ChartKind, Cli, Commands, NotificationAction, TrashAction};
+use color::{Painter, Theme};
+use db::establish_connection;
+use exchange::ExchangeFormat;
+use filter::Filter;
+use journal::Journal;
+use listing::GroupBy;
+use output::{Column, Format};
+use std::fs;
 use std::io::{self, Write};
+use timesheet::TimesheetFormat;
 use todo::User;
 
 fn main() {
+    let mut conn = match establish_connection() {
+        Ok(conn) => conn,
+        Err(e) => {
+            println!("Failed to open db: {}", e);
+            return;
+        }
+    };
+    let config = config::load().unwrap_or_else(|e| {
+        println!("Ignoring config: {}", e);
+        Default::default()
+    });
+    if let Err(e) = focus::install_interrupt_handler() {
+        println!("Ctrl-C won't stop focus sessions: {}", e);
+    }
+    let theme = Theme::from_config(&config.theme).unwrap_or_else(|e| {
+        println!("Ignoring theme: {}", e);
+        Theme::default()
+    });
+    if config.archive.after_days > 0 {
+        match archive::archive_completed(&conn, config.archive.after_days) {
+            Ok(0) => {}
+            Ok(count) => println!("Archived {} completed items.", count),
+            Err(e) => println!("Failed to archive completed items: {}", e),
+        }
+    }
+    if config.trash.purge_after_days > 0 {
+        match trash::purge_expired(&conn, config.trash.purge_after_days) {
+            Ok(0) => {}
+            Ok(count) => println!("Purged {} items from the trash.", count),
+            Err(e) => println!("Failed to purge the trash: {}", e),
+        }
+    }
+    if let Err(e) = notifications::notify_overdue(&conn) {
+        println!("Failed to check due dates: {}", e);
+    }
     let mut user: Option<User> = None;
+    let mut journal: Option<Journal> = None;
 
     loop {
-        print!("> ");
+        // Logged-in users see how many unread notifications they have.
+        let unread = user
+            .as_ref()
+            .and_then(|user| notifications::unread_count(&conn, &user.user_name).ok())
+            .unwrap_or(0);
+        if unread > 0 {
+            print!("({} unread) > ", unread);
+        } else {
+            print!("> ");
+        }
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
         io::stdin().read_line(&mut input).unwrap();
 
-        let args = input.trim().split_whitespace();
+        let args = match split_args(&input) {
+            Ok(args) => args,
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            }
+        };
         let cli = Cli::try_parse_from(args);
 
         match cli {
-            Ok(cli) => match &cli.command {
-                Commands::Login { user_name, email } => {
-                    user = Some(User::new(user_name.to_string(), email.to_string()));
-                    println!("Logged in as {}.", user_name);
-                }
-                Commands::Logout => {
-                    user = None;
-                    println!("Logged out.");
-                }
-                Commands::Exit => {
-                    println!("Exiting...");
-                    break;
+            Ok(cli) => {
+                // Only the named list can change, except on import, which can
+                // add to any list.
+                let scope = cli
+                    .command
+                    .target_list()
+                    .filter(|_| !matches!(cli.command, Commands::Import { .. }))
+                    .map(String::from);
+                let changes_items = cli.command.is_mutating()
+                    || matches!(cli.command, Commands::Undo { .. } | Commands::Redo { .. });
+                let before = user
+                    .as_ref()
+                    .filter(|_| changes_items)
+                    .map(|user| journal::snapshot(user, scope.as_deref()));
+                if let (Some(user), Some(list_name)) = (&user, cli.command.target_list()) {
+                    // Viewers may still comment.
+                    let commenting = matches!(cli.command, Commands::Comment { .. });
+                    if cli.command.is_mutating() && !commenting && !user.can_edit(list_name) {
+                        println!("You can only view {}.", list_name);
+                        continue;
+                    }
                 }
-                Commands::Show {
-                    all,
-                    completed,
-                    incomplete,
-                    list_name,
-                } => {
-                    if let Some(user) = &user {
-                        if *all {
-                            user.show_lists();
-                        } else if *completed {
-                            user.show_completed_items(list_name.as_ref());
-                        } else if *incomplete {
-                            user.show_incomplete_items(list_name.as_ref());
-                        } else if let Some(name) = list_name {
-                            user.show_list_items(name);
-                        } else {
-                            println!("Invalid show command. Use --help for more information.");
+                match &cli.command {
+                    Commands::Login { user_name, email } => {
+                        user = Some(User::new(user_name.to_string(), email.to_string()));
+                        // Whatever a previous session didn't push is lost.
+                        if let Err(e) = events::discard(&conn, user_name, None) {
+                            println!("Failed to drop unpushed events: {}", e);
                         }
-                    } else {
-                        println!("Please log in first.");
+                        journal = match Journal::load(&conn, user_name) {
+                            Ok(journal) => Some(journal),
+                            Err(e) => {
+                                println!("Failed to load history: {}", e);
+                                None
+                            }
+                        };
+                        println!("Logged in as {}.", user_name);
                     }
-                }
-                Commands::Add { list_name, item } => {
-                    if let Some(user) = &mut user {
-                        if let Some(list) = user.get_todo_list(list_name) {
-                            list.add(item.to_string());
+                    Commands::Logout => {
+                        user = None;
+                        journal = None;
+                        println!("Logged out.");
+                    }
+                    Commands::Exit => {
+                        println!("Exiting...");
+                        break;
+                    }
+                    Commands::Show {
+                        all,
+                        completed,
+                        incomplete,
+                        filter,
+                        sort,
+                        archived,
+                        group_by,
+                        columns,
+                        mine,
+                        assignee,
+                        format,
+                    } => {
+                        if let Some(user) = &user {
+                            let mut parsed = match filter {
+                                Some(name) if user.todo_lists.contains_key(name) => {
+                                    Some(Filter::list(name))
+                                }
+                                Some(expr) => match filter::parse(expr) {
+                                    Ok(parsed) => Some(parsed),
+                                    Err(e) => {
+                                        println!("Invalid filter: {}", e);
+                                        println!("  {}", expr);
+                                        println!("  {}^", " ".repeat(e.position));
+                                        continue;
+                                    }
+                                },
+                                None => None,
+                            };
+                            if *completed || *incomplete {
+                                let status = if *completed {
+                                    Filter::Done
+                                } else {
+                                    Filter::Not(Box::new(Filter::Done))
+                                };
+                                parsed = Some(match parsed {
+                                    Some(parsed) => parsed.and(status),
+                                    None => status,
+                                });
+                            }
+                            let assignee = if *mine {
+                                Some(&user.user_name)
+                            } else {
+                                assignee.as_ref()
+                            };
+                            if let Some(assignee) = assignee {
+                                let assigned = Filter::assignee(assignee);
+                                parsed = Some(match parsed {
+                                    Some(parsed) => parsed.and(assigned),
+                                    None => assigned,
+                                });
+                            }
+                            if parsed.is_none() && !*all {
+                                println!("Invalid show command. Use --help for more information.");
+                                continue;
+                            }
+                            let mut rows = listing::rows(user, parsed.as_ref(), *archived);
+                            listing::sort_rows(&mut rows, sort);
+                            output::print_listing(
+                                &listing::group_rows(rows, *group_by),
+                                *group_by,
+                                columns,
+                                *format,
+                                &Painter::new(cli.color, &theme),
+                            );
                         } else {
-                            user.add_todo_list(list_name.to_string());
+                            println!("Please log in first.");
+                        }
+                    }
+                    Commands::Add { list_name, item } => {
+                        if let Some(user) = &mut user {
                             if let Some(list) = user.get_todo_list(list_name) {
                                 list.add(item.to_string());
+                            } else {
+                                user.add_todo_list(list_name.to_string());
+                                if let Some(list) = user.get_todo_list(list_name) {
+                                    list.add(item.to_string());
+                                }
                             }
+                        } else {
+                            println!("Please log in first.");
                         }
-                    } else {
-                        println!("Please log in first.");
                     }
-                }
-                Commands::Complete {
-                    list_name,
-                    item_number,
-                } => {
-                    if let Some(user) = &mut user {
-                        if let Some(list) = user.get_todo_list(list_name) {
-                            list.mark_complete(*item_number);
+                    Commands::Complete {
+                        list_name,
+                        item_number,
+                    } => {
+                        if let Some(user) = &mut user {
+                            if let Some(list) = user.get_todo_list(list_name) {
+                                list.mark_complete(*item_number);
+                            }
+                        } else {
+                            println!("Please log in first.");
                         }
-                    } else {
-                        println!("Please log in first.");
                     }
-                }
-                Commands::Incomplete {
-                    list_name,
-                    item_number,
-                } => {
-                    if let Some(user) = &mut user {
-                        if let Some(list) = user.get_todo_list(list_name) {
-                            list.mark_incomplete(*item_number);
+                    Commands::Incomplete {
+                        list_name,
+                        item_number,
+                    } => {
+                        if let Some(user) = &mut user {
+                            if let Some(list) = user.get_todo_list(list_name) {
+                                list.mark_incomplete(*item_number);
+                            }
+                        } else {
+                            println!("Please log in first.");
                         }
-                    } else {
-                        println!("Please log in first.");
                     }
-                }
-                Commands::Remove {
-                    list_name,
-                    item_number,
-                } => {
-                    if let Some(user) = &mut user {
-                        match (list_name, item_number) {
-                            (Some(name), Some(number)) => {
-                                if let Some(list) = user.get_todo_list(name) {
-                                    list.remove_item(*number);
+                    Commands::Note {
+                        list_name,
+                        item_number,
+                        text,
+                    } => {
+                        if let Some(user) = &mut user {
+                            if let Some(list) = user.get_todo_list(list_name) {
+                                if !text.is_empty() {
+                                    list.append_note(*item_number, &text.join(" "));
+                                } else if let Some(todo) = list.get_item(*item_number) {
+                                    match editor::edit_text(&todo.notes) {
+                                        Ok(notes) => list.set_notes(*item_number, notes),
+                                        Err(e) => println!("Failed to edit note: {}", e),
+                                    }
+                                }
+                            }
+                        } else {
+                            println!("Please log in first.");
+                        }
+                    }
+                    Commands::Info {
+                        list_name,
+                        item_number,
+                        format,
+                    } => {
+                        if let Some(user) = &user {
+                            match listing::find(user, list_name, *item_number) {
+                                Some(row) => {
+                                    let tracked =
+                                        timesheet::tracked(&conn, &user.user_name, &row.todo.uid)
+                                            .unwrap_or_default();
+                                    output::print_item(
+                                        &row,
+                                        &tracked,
+                                        *format,
+                                        &Painter::new(cli.color, &theme),
+                                    )
+                                }
+                                None => println!("No item {} in list {}.", item_number, list_name),
+                            }
+                        } else {
+                            println!("Please log in first.");
+                        }
+                    }
+                    Commands::Agenda { format } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let rows = listing::agenda(user);
+                        if rows.is_empty() && *format == Format::Text {
+                            println!("Nothing is due.");
+                            continue;
+                        }
+                        output::print_listing(
+                            &listing::group_rows(rows, GroupBy::Due),
+                            GroupBy::Due,
+                            &[Column::List, Column::Id, Column::Title, Column::Due],
+                            *format,
+                            &Painter::new(cli.color, &theme),
+                        );
+                    }
+                    Commands::Search {
+                        query,
+                        archived,
+                        sort,
+                        group_by,
+                        format,
+                    } => {
+                        if let Some(user) = &user {
+                            match search::search(user, &query.join(" "), *archived) {
+                                Ok(mut hits) => {
+                                    listing::sort_by_row(&mut hits, sort, |hit| hit.row);
+                                    output::print_search(
+                                        &listing::group_by_row(hits, *group_by, |hit| hit.row),
+                                        *group_by,
+                                        *format,
+                                        &Painter::new(cli.color, &theme),
+                                    )
+                                }
+                                Err(e) => println!("Search failed: {}", e),
+                            }
+                        } else {
+                            println!("Please log in first.");
+                        }
+                    }
+                    Commands::Stats {
+                        period,
+                        periods,
+                        format,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let stats =
+                            stats::stats(user, *period, *periods, 5, Local::now().naive_local());
+                        output::print_stats(
+                            &stats,
+                            *period,
+                            *format,
+                            &Painter::new(cli.color, &theme),
+                        );
+                    }
+                    Commands::Chart { kind } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let today = Local::now().date_naive();
+                        let lines = match kind {
+                            ChartKind::Heatmap => {
+                                chart::heatmap(&chart::completion_dates(user), today)
+                            }
+                            ChartKind::Burndown { list_name, days } => {
+                                let Some(list) = user.todo_lists.get(list_name) else {
+                                    println!("No list named {}.", list_name);
+                                    continue;
+                                };
+                                let from = today - Duration::days(i64::from(*days.max(&1)) - 1);
+                                chart::burndown(&chart::list_history(list), from, today, 10)
+                            }
+                        };
+                        for line in lines {
+                            println!("{}", line);
+                        }
+                    }
+                    Commands::Start {
+                        list_name,
+                        item_number,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let Some(todo) = user
+                            .todo_lists
+                            .get(list_name)
+                            .and_then(|list| list.get_item(*item_number))
+                            .filter(|todo| !todo.is_deleted)
+                        else {
+                            println!("No item {} in list {}.", item_number, list_name);
+                            continue;
+                        };
+                        match timesheet::start(&conn, &user.user_name, list_name, todo) {
+                            Ok(stopped) => {
+                                if let Some((timer, seconds)) = stopped {
+                                    println!(
+                                        "Stopped {} {} after {}.",
+                                        timer.list_name,
+                                        timer.item_number,
+                                        timesheet::format_duration(seconds)
+                                    );
+                                }
+                                println!("Started {} {}: {}", list_name, item_number, todo.title);
+                            }
+                            Err(e) => println!("Failed to start the timer: {}", e),
+                        }
+                    }
+                    Commands::Stop => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        match timesheet::stop(&conn, &user.user_name) {
+                            Ok(Some((timer, seconds))) => println!(
+                                "Stopped {} {} after {}.",
+                                timer.list_name,
+                                timer.item_number,
+                                timesheet::format_duration(seconds)
+                            ),
+                            Ok(None) => println!("No timer is running."),
+                            Err(e) => println!("Failed to stop the timer: {}", e),
+                        }
+                    }
+                    Commands::LogTime {
+                        duration,
+                        list_name,
+                        item_number,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let seconds = match timesheet::parse_duration(duration) {
+                            Ok(seconds) => seconds,
+                            Err(e) => {
+                                println!("{}", e);
+                                continue;
+                            }
+                        };
+                        let (list_name, item_number) = match (list_name, item_number) {
+                            (Some(list_name), Some(item_number)) => {
+                                (list_name.clone(), *item_number)
+                            }
+                            (Some(_), None) => {
+                                println!("Please give an item number too.");
+                                continue;
+                            }
+                            _ => match timesheet::active(&conn, &user.user_name) {
+                                Ok(Some(timer)) => (timer.list_name, timer.item_number),
+                                Ok(None) => {
+                                    println!("No timer is running; name the item to log time on.");
+                                    continue;
                                 }
+                                Err(e) => {
+                                    println!("Failed to read the timer: {}", e);
+                                    continue;
+                                }
+                            },
+                        };
+                        let Some(todo) = user
+                            .todo_lists
+                            .get(&list_name)
+                            .and_then(|list| list.get_item(item_number))
+                            .filter(|todo| !todo.is_deleted)
+                        else {
+                            println!("No item {} in list {}.", item_number, list_name);
+                            continue;
+                        };
+                        match timesheet::log(
+                            &conn,
+                            &user.user_name,
+                            &list_name,
+                            todo,
+                            seconds,
+                            false,
+                        ) {
+                            Ok(()) => println!(
+                                "Logged {} on {} {}.",
+                                timesheet::format_duration(seconds),
+                                list_name,
+                                item_number
+                            ),
+                            Err(e) => println!("Failed to log time: {}", e),
+                        }
+                    }
+                    Commands::Focus {
+                        list_name,
+                        item_number,
+                        work,
+                        rest,
+                        rounds,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let Some(todo) = user
+                            .todo_lists
+                            .get(list_name)
+                            .and_then(|list| list.get_item(*item_number))
+                            .filter(|todo| !todo.is_deleted)
+                        else {
+                            println!("No item {} in list {}.", item_number, list_name);
+                            continue;
+                        };
+                        println!(
+                            "Focusing on {} {}: {}. Press Ctrl-C to stop.",
+                            list_name, item_number, todo.title
+                        );
+                        match focus::run(
+                            &conn,
+                            &user.user_name,
+                            list_name,
+                            todo,
+                            *work as u64,
+                            *rest as u64,
+                            *rounds,
+                        ) {
+                            Ok(finished) => {
+                                println!("Finished {} of {} pomodoros.", finished, rounds)
                             }
-                            (Some(name), None) => {
-                                user.todo_lists.remove(name);
+                            Err(e) => println!("Failed to log the session: {}", e),
+                        }
+                    }
+                    Commands::Timesheet {
+                        week,
+                        format,
+                        output,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let since = week.then(|| {
+                            let today = Local::now().date_naive();
+                            let monday = today
+                                - Duration::days(today.weekday().num_days_from_monday().into());
+                            format!("{} 00:00:00", monday.format("%Y-%m-%d"))
+                        });
+                        let lines = match timesheet::summarise(&conn, user, since.as_deref()) {
+                            Ok(lines) => lines,
+                            Err(e) => {
+                                println!("Failed to read time entries: {}", e);
+                                continue;
                             }
-                            (None, None) => {
-                                user.todo_lists.clear();
+                        };
+                        let text = match format {
+                            TimesheetFormat::Text => timesheet::to_text(&lines),
+                            TimesheetFormat::Csv => timesheet::to_csv(&lines),
+                        };
+                        match output {
+                            Some(path) => {
+                                if let Err(e) = fs::write(path, text) {
+                                    println!("Failed to write {}: {}", path.display(), e);
+                                }
                             }
-                            _ => {
+                            None => print!("{}", text),
+                        }
+                    }
+                    Commands::Import { file, from, list } => {
+                        if let Some(user) = &mut user {
+                            let Some(format) = from.or_else(|| ExchangeFormat::from_path(file))
+                            else {
                                 println!(
-                                    "Invalid remove command. Use --help for more information."
+                                    "Can't tell the format of {}; pass --from.",
+                                    file.display()
                                 );
+                                continue;
+                            };
+                            match fs::read_to_string(file) {
+                                Ok(text) => {
+                                    let foreign =
+                                        match exchange::foreign_uids(&conn, &user.user_name) {
+                                            Ok(foreign) => foreign,
+                                            Err(e) => {
+                                                println!("Failed to check item ids: {}", e);
+                                                continue;
+                                            }
+                                        };
+                                    let report =
+                                        exchange::import(user, format, &text, list, &foreign);
+                                    for (line, error) in &report.errors {
+                                        println!("{}:{}: {}", file.display(), line, error);
+                                    }
+                                    println!(
+                                        "Imported {} items, updated {}, skipped {}.",
+                                        report.imported,
+                                        report.updated,
+                                        report.errors.len()
+                                    );
+                                }
+                                Err(e) => println!("Failed to read {}: {}", file.display(), e),
                             }
+                        } else {
+                            println!("Please log in first.");
                         }
-                    } else {
-                        println!("Please log in first.");
                     }
-                }
-                Commands::Push => {
-                    if let Some(user) = &user {
-                        if let Err(e) = user.push_to_db() {
-                            println!("Failed to push to db: {}", e);
+                    Commands::Export {
+                        format,
+                        list_name,
+                        output,
+                    } => {
+                        if let Some(user) = &user {
+                            match exchange::export(user, *format, list_name.as_deref()) {
+                                Ok(text) => {
+                                    match output {
+                                        Some(path) => {
+                                            if let Err(e) = fs::write(path, text) {
+                                                println!(
+                                                    "Failed to write {}: {}",
+                                                    path.display(),
+                                                    e
+                                                );
+                                                continue;
+                                            }
+                                        }
+                                        None => print!("{}", text),
+                                    }
+                                    let left_out =
+                                        exchange::left_out(user, *format, list_name.as_deref());
+                                    if !left_out.is_empty() {
+                                        println!(
+                                            "Left out {}: the format has no place for them.",
+                                            left_out.join(", ")
+                                        );
+                                    }
+                                }
+                                Err(e) => println!("{}", e),
+                            }
+                        } else {
+                            println!("Please log in first.");
                         }
-                    } else {
-                        println!("Please log in first.");
                     }
-                }
-                Commands::Pull { user_name } => {
-                    if let Some(user) = &mut user {
-                        match User::pull_from_db(user_name) {
-                            Ok(pulled_user) => *user = pulled_user,
-                            Err(e) => println!("Failed to pull from db: {}", e),
+                    Commands::Dump { output } => match dump::dump(&conn) {
+                        Ok(dump) => {
+                            let text = serde_json::to_string_pretty(&dump).unwrap() + "\n";
+                            match output {
+                                Some(path) => {
+                                    if let Err(e) = fs::write(path, text) {
+                                        println!("Failed to write {}: {}", path.display(), e);
+                                    }
+                                }
+                                None => print!("{}", text),
+                            }
+                        }
+                        Err(e) => println!("Failed to read db: {}", e),
+                    },
+                    Commands::Restore { file, merge } => {
+                        let dump = match fs::read_to_string(file) {
+                            Ok(text) => dump::parse(&text),
+                            Err(e) => Err(e.to_string()),
+                        };
+                        match dump {
+                            Ok(dump) => match dump::restore(&mut conn, &dump, *merge) {
+                                Ok(report) => {
+                                    println!(
+                                "Restored {} users: {} items added, {} updated. Pull to see them.",
+                                report.users, report.inserted, report.updated
+                            );
+                                    // A full restore replaces the undo history too.
+                                    if let (false, Some(user)) = (*merge, &user) {
+                                        journal = Journal::load(&conn, &user.user_name).ok();
+                                    }
+                                }
+                                Err(e) => println!("Failed to restore: {}", e),
+                            },
+                            Err(e) => println!("Can't restore {}: {}", file.display(), e),
+                        }
+                    }
+                    Commands::Backup { action } => {
+                        let dir = match backup::snapshot_dir(&conn, config.backup.dir.as_deref()) {
+                            Ok(dir) => dir,
+                            Err(e) => {
+                                println!("{}", e);
+                                continue;
+                            }
+                        };
+                        match action {
+                            None => match backup::create(&conn, &dir) {
+                                Ok(path) => {
+                                    println!("Saved {}.", path.display());
+                                    let (daily, weekly) =
+                                        (config.backup.keep_daily, config.backup.keep_weekly);
+                                    match backup::rotate(&dir, daily, weekly) {
+                                        Ok(removed) => {
+                                            for path in removed {
+                                                println!(
+                                                    "Removed old snapshot {}.",
+                                                    path.display()
+                                                );
+                                            }
+                                        }
+                                        Err(e) => println!("Failed to remove old snapshots: {}", e),
+                                    }
+                                }
+                                Err(e) => println!("Failed to back up: {}", e),
+                            },
+                            Some(BackupAction::List) => match backup::list(&dir) {
+                                Ok(snapshots) if snapshots.is_empty() => {
+                                    println!("No snapshots in {}.", dir.display())
+                                }
+                                Ok(snapshots) => {
+                                    for (_, path) in snapshots {
+                                        println!("{}", path.file_name().unwrap().to_string_lossy());
+                                    }
+                                }
+                                Err(e) => println!("{}", e),
+                            },
+                            Some(BackupAction::Restore { snapshot }) => {
+                                let snapshot = backup::resolve(&dir, snapshot);
+                                if let Err(e) = backup::verify(&snapshot) {
+                                    println!("Not restoring: {}", e);
+                                    continue;
+                                }
+                                // Keep the current state in case the wrong snapshot was picked.
+                                match backup::create(&conn, &dir) {
+                                    Ok(path) => {
+                                        println!(
+                                            "Saved the current database as {}.",
+                                            path.display()
+                                        )
+                                    }
+                                    Err(e) => {
+                                        println!("Not restoring: failed to back up first: {}", e);
+                                        continue;
+                                    }
+                                }
+                                match backup::restore(&mut conn, &snapshot) {
+                                    Ok(()) => {
+                                        println!("Restored {}. Pull to see it.", snapshot.display())
+                                    }
+                                    Err(e) => println!("Failed to restore: {}", e),
+                                }
+                            }
+                        }
+                    }
+                    Commands::Remove {
+                        list_name,
+                        item_number,
+                    } => {
+                        if let Some(user) = &mut user {
+                            match (list_name, item_number) {
+                                (Some(name), Some(number)) => {
+                                    user.trash(name, Some(*number));
+                                }
+                                (Some(name), None) => {
+                                    if user.todo_lists.contains_key(name)
+                                        && user.manageable(name).is_none()
+                                    {
+                                        println!("Only owners can remove {} as a whole.", name);
+                                        continue;
+                                    }
+                                    user.trash(name, None);
+                                }
+                                (None, None) => {
+                                    // Lists shared with the user are left alone.
+                                    let names: Vec<String> = user
+                                        .todo_lists
+                                        .values()
+                                        .filter(|list| list.shared.is_none())
+                                        .map(|list| list.title.clone())
+                                        .collect();
+                                    for name in names {
+                                        user.trash(&name, None);
+                                    }
+                                }
+                                _ => {
+                                    println!(
+                                        "Invalid remove command. Use --help for more information."
+                                    );
+                                }
+                            }
+                        } else {
+                            println!("Please log in first.");
+                        }
+                    }
+                    Commands::Share {
+                        list_name,
+                        email,
+                        role,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        if !user.todo_lists.contains_key(list_name) {
+                            println!("No list named {}.", list_name);
+                            continue;
+                        }
+                        let Some((owner, name)) = user.manageable(list_name) else {
+                            println!("Only owners can share {}.", list_name);
+                            continue;
+                        };
+                        if owner == user.user_name {
+                            if let Err(e) = user.save_user(&conn) {
+                                println!("Failed to save user: {}", e);
+                                continue;
+                            }
+                        }
+                        let Some(email) = email else {
+                            match sharing::members(&conn, owner, name) {
+                                Ok(members) if members.is_empty() => {
+                                    println!("{} isn't shared.", list_name)
+                                }
+                                Ok(members) => {
+                                    println!("Owner: {}", owner);
+                                    for member in members {
+                                        println!(
+                                            "{} <{}>: {}",
+                                            member.user_name,
+                                            member.email,
+                                            member.role.as_str()
+                                        );
+                                    }
+                                }
+                                Err(e) => println!("Failed to read members: {}", e),
+                            }
+                            continue;
+                        };
+                        match sharing::share(&conn, owner, name, email, *role) {
+                            Ok(Some(member)) => println!(
+                                "Shared {} with {} as {}.",
+                                list_name,
+                                member,
+                                role.as_str()
+                            ),
+                            Ok(None) => println!("No user with email {}.", email),
+                            Err(e) => println!("Failed to share {}: {}", list_name, e),
+                        }
+                    }
+                    Commands::Unshare { list_name, email } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        if !user.todo_lists.contains_key(list_name) {
+                            println!("No list named {}.", list_name);
+                            continue;
+                        }
+                        let Some((owner, name)) = user.manageable(list_name) else {
+                            println!("Only owners can unshare {}.", list_name);
+                            continue;
+                        };
+                        match sharing::unshare(&conn, owner, name, email) {
+                            Ok(true) => println!("Stopped sharing {} with {}.", list_name, email),
+                            Ok(false) => println!("{} isn't shared with {}.", list_name, email),
+                            Err(e) => println!("Failed to unshare {}: {}", list_name, e),
+                        }
+                    }
+                    Commands::Archive { list_name, all } => {
+                        let Some(user) = &mut user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        match user.get_todo_list(list_name) {
+                            Some(list) => match list.archive(*all) {
+                                Ok(count) => println!("Archived {} items.", count),
+                                Err(open) => println!(
+                                    "{} still has {} unfinished items; complete them to archive the whole list.",
+                                    list_name, open
+                                ),
+                            },
+                            None => println!("No list named {}.", list_name),
+                        }
+                    }
+                    Commands::Unarchive {
+                        list_name,
+                        item_number,
+                    } => {
+                        let Some(user) = &mut user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        match user.get_todo_list(list_name) {
+                            Some(list) => {
+                                println!("Unarchived {} items.", list.unarchive(*item_number))
+                            }
+                            None => println!("No list named {}.", list_name),
+                        }
+                    }
+                    Commands::Trash {
+                        action,
+                        sort,
+                        group_by,
+                        format,
+                    } => {
+                        let Some(user) = &mut user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        match action {
+                            Some(TrashAction::Empty) => {
+                                println!("Deleted {} items for good.", user.empty_trash())
+                            }
+                            Some(TrashAction::Restore { id }) => {
+                                match user.restore_from_trash(*id) {
+                                    0 => println!("Nothing in the trash with number {}.", id),
+                                    count => println!("Restored {} items.", count),
+                                }
+                            }
+                            None => {
+                                let mut entries = trash::entries(user);
+                                listing::sort_by_row(&mut entries, sort, |entry| entry.row());
+                                output::print_trash(
+                                    &listing::group_by_row(entries, *group_by, |entry| entry.row()),
+                                    *group_by,
+                                    *format,
+                                    &Painter::new(cli.color, &theme),
+                                );
+                            }
+                        }
+                    }
+                    Commands::Due {
+                        list_name,
+                        item_number,
+                        date,
+                    } => {
+                        let Some(user) = &mut user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        match user.get_todo_list(list_name) {
+                            Some(list) => {
+                                if !list.set_due(*item_number, date.clone()) {
+                                    println!("No item {} in list {}.", item_number, list_name);
+                                }
+                            }
+                            None => println!("No list named {}.", list_name),
+                        }
+                    }
+                    Commands::Assign {
+                        list_name,
+                        item_number,
+                        user: assignee,
+                    } => {
+                        let Some(user) = &mut user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let Some((owner, name)) = user.origin(list_name) else {
+                            println!("No list named {}.", list_name);
+                            continue;
+                        };
+                        if let Some(assignee) = assignee {
+                            match sharing::has_access(&conn, owner, name, assignee) {
+                                Ok(true) => {}
+                                Ok(false) => {
+                                    println!("{} can't see {}.", assignee, list_name);
+                                    continue;
+                                }
+                                Err(e) => {
+                                    println!("Failed to check access: {}", e);
+                                    continue;
+                                }
+                            }
+                        }
+                        if let Some(list) = user.get_todo_list(list_name) {
+                            if !list.assign(*item_number, assignee.clone()) {
+                                println!("No item {} in list {}.", item_number, list_name);
+                            }
+                        }
+                    }
+                    Commands::Comment {
+                        list_name,
+                        item_number,
+                        text,
+                    } => {
+                        let Some(user) = &mut user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let author = user.user_name.clone();
+                        match user.get_todo_list(list_name) {
+                            Some(list) => {
+                                if !list.comment(*item_number, &author, text.to_string()) {
+                                    println!("No item {} in list {}.", item_number, list_name);
+                                }
+                            }
+                            None => println!("No list named {}.", list_name),
+                        }
+                    }
+                    Commands::Notifications {
+                        unread,
+                        action,
+                        sort,
+                        group_by,
+                        format,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let name = &user.user_name;
+                        match action {
+                            Some(NotificationAction::MarkRead { id, .. }) => {
+                                match (notifications::mark_read(&conn, name, *id), id) {
+                                    (Ok(0), Some(id)) => {
+                                        println!("No unread notification {}.", id)
+                                    }
+                                    (Ok(count), _) => {
+                                        println!("Marked {} notifications as read.", count)
+                                    }
+                                    (Err(e), _) => {
+                                        println!("Failed to mark notifications: {}", e)
+                                    }
+                                }
+                            }
+                            None => match notifications::list(&conn, name, *unread) {
+                                Ok(mut list) => {
+                                    notifications::sort(&mut list, sort);
+                                    output::print_notifications(
+                                        &notifications::group(list, *group_by),
+                                        *format,
+                                    );
+                                }
+                                Err(e) => println!("Failed to read notifications: {}", e),
+                            },
+                        }
+                    }
+                    Commands::Undo { count } | Commands::Redo { count } => {
+                        let (Some(user), Some(journal)) = (&mut user, &mut journal) else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let (result, name, verb) = match cli.command {
+                            Commands::Undo { .. } => {
+                                (journal.undo(&conn, user, *count), "undo", "Undid")
+                            }
+                            _ => (journal.redo(&conn, user, *count), "redo", "Redid"),
+                        };
+                        match result {
+                            Ok(replayed) if replayed == Default::default() => {
+                                println!("Nothing to {}.", name)
+                            }
+                            Ok(replayed) => {
+                                for command in replayed.commands {
+                                    println!("{} '{}'.", verb, command);
+                                }
+                                if let Some((command, reason)) = replayed.refused {
+                                    println!("Can't {} '{}': {}.", name, command, reason);
+                                }
+                            }
+                            Err(e) => println!("Failed to update history: {}", e),
+                        }
+                    }
+                    Commands::Log {
+                        list_name,
+                        item_number,
+                        format,
+                    } => {
+                        let Some(user) = &user else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        let uid = match user
+                            .todo_lists
+                            .get(list_name)
+                            .and_then(|list| list.get_item(*item_number))
+                        {
+                            Some(todo) => Ok(Some(todo.uid.clone())),
+                            // Events are filed under the owner and the list's own name.
+                            None => {
+                                let (owner, name) = user
+                                    .origin(list_name)
+                                    .unwrap_or((&user.user_name, list_name));
+                                events::find_uid(&conn, owner, name, *item_number)
+                            }
+                        };
+                        match uid.and_then(|uid| match uid {
+                            Some(uid) => events::timeline(&conn, &uid, &user.user_name),
+                            None => Ok(Vec::new()),
+                        }) {
+                            Ok(timeline) => {
+                                output::print_events(&timeline, list_name, *item_number, *format)
+                            }
+                            Err(e) => println!("Failed to read events: {}", e),
+                        }
+                    }
+                    Commands::History { limit, format } => {
+                        let Some(journal) = &journal else {
+                            println!("Please log in first.");
+                            continue;
+                        };
+                        output::print_history(&journal.operations, *limit, *format);
+                    }
+                    Commands::Push => {
+                        if let Some(user) = &mut user {
+                            if let Err(e) = user.push_to_db(&conn) {
+                                println!("Failed to push to db: {}", e);
+                            }
+                        } else {
+                            println!("Please log in first.");
+                        }
+                    }
+                    Commands::Pull { user_name } => {
+                        if let Some(user) = &mut user {
+                            match User::pull_from_db(&conn, user_name) {
+                                Ok(pulled_user) => {
+                                    // Changes not pushed are gone, and so are their events.
+                                    if let Err(e) = events::discard(&conn, &user.user_name, None) {
+                                        println!("Failed to drop unpushed events: {}", e);
+                                    }
+                                    *user = pulled_user;
+                                }
+                                Err(e) => println!("Failed to pull from db: {}", e),
+                            }
+                        } else {
+                            println!("Please log in first.");
                         }
-                    } else {
-                        println!("Please log in first.");
                     }
                 }
-                _ => {
-                    println!("Invalid command. Use --help for more information.");
+                if let (Some(before), Some(user)) = (&before, &user) {
+                    let after = journal::snapshot(user, scope.as_deref());
+                    if let Err(e) = events::queue(&conn, user, before, &after) {
+                        println!("Failed to record events: {}", e);
+                    }
+                    if let Some(journal) = journal.as_mut().filter(|_| cli.command.is_mutating()) {
+                        // Record the line as typed, without the leading `todo`.
+                        let command = input
+                            .split_whitespace()
+                            .skip(1)
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        if let Err(e) = journal.record(&conn, &command, before, &after) {
+                            println!("Failed to record history: {}", e);
+                        }
+                    }
                 }
-            },
+            }
             Err(err) => {
                 println!("Error: {}", err);
                 println!("Please use a valid command or type 'exit' to quit.");
@@ -0,0 +1,231 @@
+//! The notifications inbox. Notifications are stored per user name when
+//! something happens to the user that they didn't do themselves, such as a
+//! list being shared with them, an item assigned to them changing, being
+//! mentioned in a comment or an item of theirs going past its due date, and
+//! stay until the database is wiped; reading one only marks it.
+
+use crate::journal::Snapshot;
+use crate::listing::{compare_keys, parse_key, SortKey};
+use crate::sharing;
+use crate::todo::get_current_date_time;
+use chrono::Local;
+use clap::ValueEnum;
+use rusqlite::{params, Connection, Result};
+use serde::Serialize;
+use std::cmp::Ordering;
+
+#[derive(Serialize)]
+pub struct Notification {
+    pub id: i64,
+    /// `share`, `unshare`, `assign`, `unassign`, `change`, `mention` or `due`.
+    pub kind: String,
+    pub message: String,
+    pub created_at: String,
+    pub read_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Created,
+    Kind,
+    /// Unread first.
+    Status,
+}
+
+const SORT_FIELDS: &[(&str, SortField)] = &[
+    ("created", SortField::Created),
+    ("kind", SortField::Kind),
+    ("status", SortField::Status),
+];
+
+/// Parses `field`, `field:asc` or `field:desc`, for `notifications --sort`.
+pub fn parse_sort_key(raw: &str) -> std::result::Result<SortKey<SortField>, String> {
+    parse_key(raw, SORT_FIELDS)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    Kind,
+    /// Unread and read.
+    Status,
+    None,
+}
+
+fn compare(a: &Notification, b: &Notification, field: SortField) -> Ordering {
+    match field {
+        SortField::Created => a.id.cmp(&b.id),
+        SortField::Kind => a.kind.cmp(&b.kind),
+        SortField::Status => a.read_at.is_some().cmp(&b.read_at.is_some()),
+    }
+}
+
+/// Stable sort by each key in turn, so ties stay newest first.
+pub fn sort(notifications: &mut [Notification], keys: &[SortKey<SortField>]) {
+    notifications.sort_by(|a, b| compare_keys(a, b, keys, compare));
+}
+
+/// Splits sorted notifications into titled groups, keeping their order
+/// within a group.
+pub fn group(notifications: Vec<Notification>, by: GroupBy) -> Vec<(String, Vec<Notification>)> {
+    let mut groups: Vec<(String, Vec<Notification>)> = Vec::new();
+    for notification in notifications {
+        let name = match by {
+            GroupBy::Kind => notification.kind.clone(),
+            GroupBy::Status if notification.read_at.is_none() => "Unread".to_string(),
+            GroupBy::Status => "Read".to_string(),
+            GroupBy::None => String::new(),
+        };
+        match groups.iter_mut().find(|(group, _)| *group == name) {
+            Some((_, members)) => members.push(notification),
+            None => groups.push((name, vec![notification])),
+        }
+    }
+    match by {
+        GroupBy::Kind => groups.sort_by(|(a, _), (b, _)| a.cmp(b)),
+        GroupBy::Status => groups.sort_by_key(|(name, _)| name == "Read"),
+        GroupBy::None => {}
+    }
+    groups
+}
+
+pub fn notify(conn: &Connection, user_name: &str, kind: &str, message: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO notifications (user_name, kind, message, created_at)
+        VALUES (?1, ?2, ?3, ?4)",
+        params![user_name, kind, message, get_current_date_time()],
+    )?;
+    Ok(())
+}
+
+/// Lets assignees know about changes `actor` pushed to `owner`'s lists,
+/// given the stored items before and after: being assigned or unassigned, and
+/// changes to items that stay assigned to them. Nobody is told about their own
+/// changes.
+pub fn notify_assignees(
+    conn: &Connection,
+    actor: &str,
+    owner: &str,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> Result<()> {
+    for (uid, (list_name, new)) in after {
+        let old = before.get(uid).map(|(_, old)| old);
+        let old_assignee = old.and_then(|old| old.assignee.as_deref());
+        if let Some(assignee) = new.assignee.as_deref().filter(|name| *name != actor) {
+            let list = sharing::seen_as(owner, list_name, assignee);
+            if old_assignee != Some(assignee) {
+                let message = format!("{} assigned you \"{}\" in {}.", actor, new.title, list);
+                notify(conn, assignee, "assign", &message)?;
+            } else if let Some(old) = old.filter(|old| *old != new) {
+                let change = if old.trash.is_none() && new.trash.is_some() {
+                    "deleted"
+                } else if new.comments.len() > old.comments.len() {
+                    "commented on"
+                } else if !old.completed && new.completed {
+                    "completed"
+                } else {
+                    "changed"
+                };
+                let message = format!("{} {} \"{}\" in {}.", actor, change, new.title, list);
+                notify(conn, assignee, "change", &message)?;
+            }
+        }
+        if let Some(assignee) = old_assignee.filter(|name| *name != actor) {
+            if new.assignee.as_deref() != Some(assignee) {
+                let list = sharing::seen_as(owner, list_name, assignee);
+                let message = format!(
+                    "{} unassigned you from \"{}\" in {}.",
+                    actor, new.title, list
+                );
+                notify(conn, assignee, "unassign", &message)?;
+            }
+        }
+    }
+    for (uid, (list_name, old)) in before {
+        if after.contains_key(uid) {
+            continue;
+        }
+        if let Some(assignee) = old.assignee.as_deref().filter(|name| *name != actor) {
+            let list = sharing::seen_as(owner, list_name, assignee);
+            let message = format!("{} deleted \"{}\" in {}.", actor, old.title, list);
+            notify(conn, assignee, "change", &message)?;
+        }
+    }
+    Ok(())
+}
+
+/// Tells the assignee, or else the owner, of each open item whose due date
+/// has passed, once per due date. Returns how many were told.
+pub fn notify_overdue(conn: &Connection) -> Result<usize> {
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let tx = conn.unchecked_transaction()?;
+    let overdue: Vec<(i64, String, String, String, String, Option<String>)> = {
+        let mut stmt = tx.prepare(
+            "SELECT t.id, u.user_name, t.list_name, t.title, t.due, a.user_name
+            FROM todos t JOIN users u ON u.id = t.user_id
+            LEFT JOIN users a ON a.id = t.assignee_id
+            WHERE t.due IS NOT NULL AND substr(t.due, 1, 10) < ?1 AND t.due_notified = 0
+                AND t.is_completed = 0 AND t.trash_id IS NULL AND t.archived_at IS NULL",
+        )?;
+        let rows = stmt.query_map(params![today], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?;
+        rows.collect::<Result<_>>()?
+    };
+    for (id, owner, list_name, title, due, assignee) in &overdue {
+        let user_name = assignee.as_deref().unwrap_or(owner);
+        let list = sharing::seen_as(owner, list_name, user_name);
+        let message = format!("\"{}\" in {} was due {}.", title, list, due);
+        notify(&tx, user_name, "due", &message)?;
+        tx.execute(
+            "UPDATE todos SET due_notified = 1 WHERE id = ?1",
+            params![id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(overdue.len())
+}
+
+/// The user's notifications, newest first.
+pub fn list(conn: &Connection, user_name: &str, unread: bool) -> Result<Vec<Notification>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, kind, message, created_at, read_at FROM notifications
+        WHERE user_name = ?1 AND (?2 = 0 OR read_at IS NULL)
+        ORDER BY id DESC",
+    )?;
+    let notifications = stmt.query_map(params![user_name, unread], |row| {
+        Ok(Notification {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            message: row.get(2)?,
+            created_at: row.get(3)?,
+            read_at: row.get(4)?,
+        })
+    })?;
+    notifications.collect()
+}
+
+pub fn unread_count(conn: &Connection, user_name: &str) -> Result<usize> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM notifications WHERE user_name = ?1 AND read_at IS NULL",
+        params![user_name],
+        |row| row.get(0),
+    )
+}
+
+/// Marks one notification, or every unread one when `id` is `None`, as read.
+/// Returns how many were marked.
+pub fn mark_read(conn: &Connection, user_name: &str, id: Option<i64>) -> Result<usize> {
+    conn.execute(
+        "UPDATE notifications SET read_at = ?3
+        WHERE user_name = ?1 AND (?2 IS NULL OR id = ?2) AND read_at IS NULL",
+        params![user_name, id, get_current_date_time()],
+    )
+}
@@ -0,0 +1,853 @@
+//! Rendering for the read commands (`show`, `info`, `search`, `stats`,
+//! `notifications`, `log`, `history`, `trash`).
+//!
+//! The JSON schema is versioned by [`SCHEMA_VERSION`]; fields are only ever
+//! added, never renamed or removed, without bumping it.
+//!
+//! - item: `{"list", "item", "title", "created", "completed", "completed_at",
+//!   "priority", "tags", "notes", "assignee", "due"}`
+//! - comment: `{"uid", "author", "created_at", "text"}`
+//! - list: `{"name", "items": [item]}`
+//! - search hit: `{"list", "item", "title", "completed", "snippet"}`, with
+//!   matches in `title` and `snippet` wrapped in `[` `]`
+//!
+//! `show --format json` prints `{"version", "lists": [list]}`, `info` prints
+//! `{"version", "item": item, "tracked_seconds", "pomodoros", "comments": [comment]}`
+//! and `search` prints `{"version", "hits": [hit]}`.
+//! `stats` prints `{"version", "periods", "lists", "average_days_to_complete",
+//! "oldest_open"}`.
+//! `notifications` prints `{"version", "notifications": [{"id", "kind",
+//! "message", "created_at", "read_at"}]}`, `log` prints `{"version", "events":
+//! [{"actor", "at", "kind", "field", "old_value", "new_value"}]}`, `history`
+//! prints `{"version", "operations": [{"step", "at", "command", "undone"}]}`
+//! and `trash` prints `{"version", "trash": [{"id", "deleted_at",
+//! "deleted_by", "list", "items": [item]}]}`.
+//! `ndjson`, `csv` and `tsv` print one item or hit per line, or for `stats`
+//! one period; the other commands print one of their records per line.
+
+use crate::color::Painter;
+use crate::events::Event;
+use crate::journal::Operation;
+use crate::listing::{GroupBy, Row};
+use crate::notifications::Notification;
+use crate::search::{has_match, mark_matches, SearchHit};
+use crate::stats::{Period, Stats};
+use crate::table;
+use crate::timesheet::{format_duration, Tracked};
+use crate::trash::Entry;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+/// Columns of the text table printed by `show`.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Column {
+    Id,
+    List,
+    Title,
+    Created,
+    Done,
+    Completed,
+    Due,
+    Priority,
+    Tags,
+    Notes,
+    Assignee,
+}
+
+impl Column {
+    fn header(self) -> &'static str {
+        match self {
+            Column::Id => "#",
+            Column::List => "List",
+            Column::Title => "Title",
+            Column::Created => "Created",
+            Column::Done => "Done",
+            Column::Completed => "Completed",
+            Column::Due => "Due",
+            Column::Priority => "Pri",
+            Column::Tags => "Tags",
+            Column::Notes => "Notes",
+            Column::Assignee => "Assignee",
+        }
+    }
+
+    fn cell(self, row: &Row) -> String {
+        let todo = row.todo;
+        match self {
+            Column::Id => todo.item_number.to_string(),
+            Column::List => row.list_name.to_string(),
+            Column::Title => todo.title.clone(),
+            Column::Created => todo
+                .datetime
+                .get(..10)
+                .unwrap_or(&todo.datetime)
+                .to_string(),
+            Column::Done if todo.is_completed => "✓".to_string(),
+            Column::Done => String::new(),
+            Column::Completed => todo
+                .completed_at
+                .as_deref()
+                .map_or("", |at| at.get(..10).unwrap_or(at))
+                .to_string(),
+            Column::Due => todo
+                .due
+                .as_deref()
+                .map_or("", |due| due.get(..16).unwrap_or(due))
+                .to_string(),
+            Column::Priority => todo.priority.map(String::from).unwrap_or_default(),
+            Column::Tags => todo.tags.join(" "),
+            Column::Notes => todo.notes.clone(),
+            Column::Assignee => todo.assignee.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ItemRecord<'a> {
+    pub list: &'a str,
+    pub item: usize,
+    pub title: &'a str,
+    pub created: &'a str,
+    pub completed: bool,
+    pub completed_at: Option<&'a str>,
+    pub priority: Option<char>,
+    pub tags: &'a [String],
+    pub notes: &'a str,
+    pub assignee: Option<&'a str>,
+    pub due: Option<&'a str>,
+}
+
+impl<'a> From<&Row<'a>> for ItemRecord<'a> {
+    fn from(row: &Row<'a>) -> Self {
+        ItemRecord {
+            list: row.list_name,
+            item: row.todo.item_number,
+            title: &row.todo.title,
+            created: &row.todo.datetime,
+            completed: row.todo.is_completed,
+            completed_at: row.todo.completed_at.as_deref(),
+            priority: row.todo.priority,
+            tags: &row.todo.tags,
+            notes: &row.todo.notes,
+            assignee: row.todo.assignee.as_deref(),
+            due: row.todo.due.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ListRecord<'a> {
+    name: &'a str,
+    items: Vec<ItemRecord<'a>>,
+}
+
+const ITEM_COLUMNS: &[&str] = &[
+    "list",
+    "item",
+    "title",
+    "created",
+    "completed",
+    "completed_at",
+    "priority",
+    "tags",
+    "notes",
+    "assignee",
+    "due",
+];
+
+fn item_fields(record: &ItemRecord) -> Vec<String> {
+    vec![
+        record.list.to_string(),
+        record.item.to_string(),
+        record.title.to_string(),
+        record.created.to_string(),
+        record.completed.to_string(),
+        record.completed_at.unwrap_or("").to_string(),
+        record.priority.map(String::from).unwrap_or_default(),
+        record.tags.join(" "),
+        record.notes.to_string(),
+        record.assignee.unwrap_or("").to_string(),
+        record.due.unwrap_or("").to_string(),
+    ]
+}
+
+const HIT_COLUMNS: &[&str] = &["list", "item", "title", "completed", "snippet"];
+
+fn bracketed(text: &str) -> String {
+    mark_matches(text, |m| format!("[{}]", m))
+}
+
+fn hit_fields(hit: &SearchHit) -> Vec<String> {
+    vec![
+        hit.row.list_name.to_string(),
+        hit.row.todo.item_number.to_string(),
+        bracketed(&hit.title),
+        hit.row.todo.is_completed.to_string(),
+        bracketed(&hit.snippet),
+    ]
+}
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn markdown_field(field: &str) -> String {
+    field.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// Prints a header and one line per record for the delimited and Markdown
+/// formats.
+fn print_table(format: Format, columns: &[&str], records: &[Vec<String>]) {
+    let line = |fields: &[String]| match format {
+        Format::Csv => fields
+            .iter()
+            .map(|f| csv_field(f))
+            .collect::<Vec<_>>()
+            .join(","),
+        Format::Tsv => fields
+            .iter()
+            .map(|f| tsv_field(f))
+            .collect::<Vec<_>>()
+            .join("\t"),
+        _ => format!(
+            "| {} |",
+            fields
+                .iter()
+                .map(|f| markdown_field(f))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ),
+    };
+    let header: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+    println!("{}", line(&header));
+    if format == Format::Markdown {
+        println!("|{}", " --- |".repeat(columns.len()));
+    }
+    for record in records {
+        println!("{}", line(record));
+    }
+}
+
+/// Prints the groups as one table, with column widths shared across groups.
+fn print_group_header(name: &str, by: GroupBy, painter: &Painter) {
+    let header = match by {
+        GroupBy::List => format!("List: {}", name),
+        GroupBy::Status | GroupBy::Due => format!("{}:", name),
+        GroupBy::Tag if name.is_empty() => "Untagged:".to_string(),
+        GroupBy::Tag => format!("Tag: {}", name),
+        GroupBy::None => return,
+    };
+    println!("{}", painter.paint(&header, |t| &t.group));
+}
+
+fn print_text_table(
+    groups: &[(String, Vec<Row>)],
+    by: GroupBy,
+    columns: &[Column],
+    painter: &Painter,
+) {
+    let default_columns: &[Column] = match by {
+        GroupBy::List => &[Column::Id, Column::Title, Column::Created, Column::Done],
+        _ => &[
+            Column::List,
+            Column::Id,
+            Column::Title,
+            Column::Created,
+            Column::Done,
+        ],
+    };
+    let columns = if columns.is_empty() {
+        default_columns
+    } else {
+        columns
+    };
+    let headers: Vec<&str> = columns.iter().map(|c| c.header()).collect();
+    let cells: Vec<Vec<Vec<String>>> = groups
+        .iter()
+        .map(|(_, rows)| {
+            rows.iter()
+                .map(|row| columns.iter().map(|c| c.cell(row)).collect())
+                .collect()
+        })
+        .collect();
+    let all: Vec<Vec<String>> = cells.iter().flatten().cloned().collect();
+    if all.is_empty() {
+        return;
+    }
+    let widths = table::column_widths(&headers, &all, table::terminal_width());
+    println!(
+        "{}",
+        painter.paint(&table::format_row(&headers, &widths), |t| &t.header)
+    );
+    for ((name, rows), cells) in groups.iter().zip(&cells) {
+        print_group_header(name, by, painter);
+        for (row, cells) in rows.iter().zip(cells) {
+            let line = table::format_row(cells, &widths);
+            if row.todo.is_completed {
+                println!("{}", painter.paint(&line, |t| &t.done));
+            } else if row.todo.is_overdue() {
+                println!("{}", painter.paint(&line, |t| &t.overdue));
+            } else if row.todo.priority == Some('A') {
+                println!("{}", painter.paint(&line, |t| &t.priority_high));
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+pub fn print_listing(
+    groups: &[(String, Vec<Row>)],
+    by: GroupBy,
+    columns: &[Column],
+    format: Format,
+    painter: &Painter,
+) {
+    // An item is listed once, even when it is in several tag groups.
+    let mut seen = HashSet::new();
+    let rows = groups
+        .iter()
+        .flat_map(|(_, rows)| rows)
+        .filter(|row| seen.insert(&row.todo.uid));
+    match format {
+        Format::Text => print_text_table(groups, by, columns, painter),
+        Format::Json => {
+            let mut lists: Vec<ListRecord> = Vec::new();
+            for row in rows {
+                let record = ItemRecord::from(row);
+                match lists.iter_mut().find(|list| list.name == row.list_name) {
+                    Some(list) => list.items.push(record),
+                    None => lists.push(ListRecord {
+                        name: row.list_name,
+                        items: vec![record],
+                    }),
+                }
+            }
+            println!("{}", json!({ "version": SCHEMA_VERSION, "lists": lists }));
+        }
+        Format::Ndjson => {
+            for row in rows {
+                println!("{}", json!(ItemRecord::from(row)));
+            }
+        }
+        Format::Csv | Format::Tsv | Format::Markdown => {
+            let records: Vec<Vec<String>> = rows
+                .map(|row| item_fields(&ItemRecord::from(row)))
+                .collect();
+            print_table(format, ITEM_COLUMNS, &records);
+        }
+    }
+}
+
+pub fn print_item(row: &Row, tracked: &Tracked, format: Format, painter: &Painter) {
+    let todo = row.todo;
+    match format {
+        Format::Text => {
+            println!("List:      {}", row.list_name);
+            println!("Item:      {}", todo.item_number);
+            if todo.is_completed {
+                println!("Title:     {}", painter.paint(&todo.title, |t| &t.done));
+            } else {
+                println!("Title:     {}", todo.title);
+            }
+            println!("Created:   {}", todo.datetime);
+            println!(
+                "Completed: {}",
+                todo.completed_at
+                    .as_deref()
+                    .unwrap_or(if todo.is_completed { "yes" } else { "no" })
+            );
+            if let Some(due) = &todo.due {
+                println!("Due:       {}", due);
+            }
+            if let Some(priority) = todo.priority {
+                println!("Priority:  {}", priority);
+            }
+            if !todo.tags.is_empty() {
+                println!("Tags:      {}", todo.tags.join(" "));
+            }
+            if let Some(assignee) = &todo.assignee {
+                println!("Assignee:  {}", assignee);
+            }
+            if tracked.seconds > 0 {
+                println!(
+                    "Time:      {} ({} pomodoros)",
+                    format_duration(tracked.seconds),
+                    tracked.pomodoros
+                );
+            }
+            if todo.notes.is_empty() {
+                println!("Notes:     (none)");
+            } else {
+                println!("Notes:");
+                for line in todo.notes.lines() {
+                    println!("  {}", line);
+                }
+            }
+            if !todo.comments.is_empty() {
+                println!("Comments:");
+                for comment in &todo.comments {
+                    println!("  {} {}:", comment.created_at, comment.author);
+                    for line in comment.text.lines() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+        }
+        Format::Json => println!(
+            "{}",
+            json!({
+                "version": SCHEMA_VERSION,
+                "item": ItemRecord::from(row),
+                "tracked_seconds": tracked.seconds,
+                "pomodoros": tracked.pomodoros,
+                "comments": todo.comments,
+            })
+        ),
+        Format::Ndjson => println!("{}", json!(ItemRecord::from(row))),
+        Format::Csv | Format::Tsv | Format::Markdown => {
+            print_table(format, ITEM_COLUMNS, &[item_fields(&ItemRecord::from(row))]);
+        }
+    }
+}
+
+fn hit_json(hit: &SearchHit) -> serde_json::Value {
+    json!({
+        "list": hit.row.list_name,
+        "item": hit.row.todo.item_number,
+        "title": bracketed(&hit.title),
+        "completed": hit.row.todo.is_completed,
+        "snippet": bracketed(&hit.snippet),
+    })
+}
+
+pub fn print_search(
+    groups: &[(String, Vec<SearchHit>)],
+    by: GroupBy,
+    format: Format,
+    painter: &Painter,
+) {
+    // A hit is listed once, even when it is in several tag groups.
+    let mut seen = HashSet::new();
+    let hits: Vec<&SearchHit> = groups
+        .iter()
+        .flat_map(|(_, hits)| hits)
+        .filter(|hit| seen.insert(&hit.row.todo.uid))
+        .collect();
+    match format {
+        Format::Text => {
+            if hits.is_empty() {
+                println!("No matches.");
+                return;
+            }
+            let highlight = |text: &str| {
+                mark_matches(text, |m| match painter.paint(m, |t| &t.highlight) {
+                    painted if painted == m => format!("[{}]", m),
+                    painted => painted,
+                })
+            };
+            // The title goes last, untruncated, as highlighting adds escapes
+            // the column widths would miscount.
+            let columns = [Column::List, Column::Id, Column::Done];
+            let headers: Vec<&str> = columns.iter().map(|c| c.header()).collect();
+            let cells = |hit: &SearchHit| -> Vec<String> {
+                columns.iter().map(|c| c.cell(&hit.row)).collect()
+            };
+            let all: Vec<Vec<String>> = hits.iter().map(|hit| cells(hit)).collect();
+            let widths = table::column_widths(&headers, &all, None);
+            let width = widths.iter().sum::<usize>() + 2 * (widths.len() - 1);
+            let lead = |cells: &[String]| table::pad(&table::format_row(cells, &widths), width);
+            let headers: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+            let header = format!("{}  Title", lead(&headers));
+            println!("{}", painter.paint(&header, |t| &t.header));
+            for (name, hits) in groups {
+                print_group_header(name, by, painter);
+                for hit in hits {
+                    println!("{}  {}", lead(&cells(hit)), highlight(&hit.title));
+                    if has_match(&hit.snippet) {
+                        println!("    {}", highlight(&hit.snippet).replace('\n', " "));
+                    }
+                }
+            }
+        }
+        Format::Json => {
+            let hits: Vec<_> = hits.into_iter().map(hit_json).collect();
+            println!("{}", json!({ "version": SCHEMA_VERSION, "hits": hits }));
+        }
+        Format::Ndjson => {
+            for hit in hits {
+                println!("{}", hit_json(hit));
+            }
+        }
+        Format::Csv | Format::Tsv | Format::Markdown => {
+            let records: Vec<Vec<String>> = hits.into_iter().map(hit_fields).collect();
+            print_table(format, HIT_COLUMNS, &records);
+        }
+    }
+}
+
+const PERIOD_COLUMNS: &[&str] = &["period", "created", "completed"];
+
+/// Prints a header and rows as an aligned text table.
+fn print_plain_table(headers: &[&str], rows: &[Vec<String>], painter: &Painter) {
+    let widths = table::column_widths(headers, rows, table::terminal_width());
+    println!(
+        "{}",
+        painter.paint(&table::format_row(headers, &widths), |t| &t.header)
+    );
+    for row in rows {
+        println!("{}", table::format_row(row, &widths));
+    }
+}
+
+pub fn print_stats(stats: &Stats, period: Period, format: Format, painter: &Painter) {
+    let periods: Vec<Vec<String>> = stats
+        .periods
+        .iter()
+        .map(|p| {
+            vec![
+                p.period.clone(),
+                p.created.to_string(),
+                p.completed.to_string(),
+            ]
+        })
+        .collect();
+    match format {
+        Format::Text => {
+            let heading = match period {
+                Period::Day => "Day",
+                Period::Week => "Week",
+                Period::Month => "Month",
+            };
+            print_plain_table(&[heading, "Created", "Completed"], &periods, painter);
+            println!();
+            let lists: Vec<Vec<String>> = stats
+                .lists
+                .iter()
+                .map(|list| {
+                    vec![
+                        list.list.clone(),
+                        list.completed.to_string(),
+                        list.total.to_string(),
+                        format!("{:.0}%", list.rate * 100.0),
+                    ]
+                })
+                .collect();
+            if !lists.is_empty() {
+                print_plain_table(&["List", "Done", "Total", "Rate"], &lists, painter);
+                println!();
+            }
+            match stats.average_days_to_complete {
+                Some(days) => println!("Average time to complete: {:.1} days", days),
+                None => println!("Average time to complete: -"),
+            }
+            if !stats.oldest_open.is_empty() {
+                println!();
+                println!("{}", painter.paint("Oldest open items:", |t| &t.group));
+                for item in &stats.oldest_open {
+                    println!(
+                        "  {} {}: {} ({} days old)",
+                        item.list, item.item, item.title, item.age_days
+                    );
+                }
+            }
+        }
+        Format::Json => println!(
+            "{}",
+            json!({
+                "version": SCHEMA_VERSION,
+                "periods": stats.periods,
+                "lists": stats.lists,
+                "average_days_to_complete": stats.average_days_to_complete,
+                "oldest_open": stats.oldest_open,
+            })
+        ),
+        Format::Ndjson => {
+            for period in &stats.periods {
+                println!("{}", json!(period));
+            }
+        }
+        Format::Csv | Format::Tsv | Format::Markdown => {
+            print_table(format, PERIOD_COLUMNS, &periods);
+        }
+    }
+}
+
+/// Prints records in one of the machine-readable formats: `json` as
+/// `{"version", key: [record]}`, `ndjson` as one record per line and the
+/// table formats as one row of `fields` per record.
+fn print_records<T: Serialize>(
+    records: &[T],
+    key: &str,
+    columns: &[&str],
+    fields: impl Fn(&T) -> Vec<String>,
+    format: Format,
+) {
+    match format {
+        Format::Json => {
+            let mut out = json!({ "version": SCHEMA_VERSION });
+            out[key] = json!(records);
+            println!("{}", out);
+        }
+        Format::Ndjson => {
+            for record in records {
+                println!("{}", json!(record));
+            }
+        }
+        _ => {
+            let rows: Vec<Vec<String>> = records.iter().map(fields).collect();
+            print_table(format, columns, &rows);
+        }
+    }
+}
+
+const NOTIFICATION_COLUMNS: &[&str] = &["id", "kind", "message", "created_at", "read_at"];
+
+pub fn print_notifications(groups: &[(String, Vec<Notification>)], format: Format) {
+    let notifications: Vec<&Notification> =
+        groups.iter().flat_map(|(_, members)| members).collect();
+    if format != Format::Text {
+        return print_records(
+            &notifications,
+            "notifications",
+            NOTIFICATION_COLUMNS,
+            |n| {
+                vec![
+                    n.id.to_string(),
+                    n.kind.clone(),
+                    n.message.clone(),
+                    n.created_at.clone(),
+                    n.read_at.clone().unwrap_or_default(),
+                ]
+            },
+            format,
+        );
+    }
+    if notifications.is_empty() {
+        println!("No notifications.");
+    }
+    for (name, members) in groups {
+        if !name.is_empty() {
+            println!("{}:", name);
+        }
+        for notification in members {
+            println!(
+                "{:>3} {} {} [{}] {}",
+                notification.id,
+                if notification.read_at.is_none() {
+                    "*"
+                } else {
+                    " "
+                },
+                notification.created_at,
+                notification.kind,
+                notification.message
+            );
+        }
+    }
+}
+
+const EVENT_COLUMNS: &[&str] = &[
+    "at",
+    "actor",
+    "kind",
+    "field",
+    "old_value",
+    "new_value",
+    "pushed",
+];
+
+pub fn print_events(events: &[Event], list_name: &str, item_number: usize, format: Format) {
+    if format != Format::Text {
+        return print_records(
+            events,
+            "events",
+            EVENT_COLUMNS,
+            |e| {
+                vec![
+                    e.at.clone(),
+                    e.actor.clone(),
+                    e.kind.clone(),
+                    e.field.clone().unwrap_or_default(),
+                    e.old_value.clone().unwrap_or_default(),
+                    e.new_value.clone().unwrap_or_default(),
+                    e.pushed.to_string(),
+                ]
+            },
+            format,
+        );
+    }
+    if events.is_empty() {
+        println!("No history for {} {}.", list_name, item_number);
+    }
+    for event in events {
+        let pending = if event.pushed { "" } else { " (not pushed)" };
+        println!(
+            "{}  {}  {}{}",
+            event.at,
+            event.actor,
+            event.describe(),
+            pending
+        );
+    }
+}
+
+#[derive(Serialize)]
+struct OperationRecord<'a> {
+    /// The count `undo` needs to revert the operation; `None` once undone.
+    step: Option<usize>,
+    at: &'a str,
+    command: &'a str,
+    undone: bool,
+}
+
+const OPERATION_COLUMNS: &[&str] = &["step", "at", "command", "undone"];
+
+/// Prints the newest `limit` operations, newest first.
+pub fn print_history(operations: &[Operation], limit: usize, format: Format) {
+    let mut steps = 0;
+    let records: Vec<OperationRecord> = operations
+        .iter()
+        .rev()
+        .map(|operation| OperationRecord {
+            step: (!operation.undone).then(|| {
+                steps += 1;
+                steps
+            }),
+            at: &operation.at,
+            command: &operation.command,
+            undone: operation.undone,
+        })
+        .take(limit)
+        .collect();
+    if format != Format::Text {
+        return print_records(
+            &records,
+            "operations",
+            OPERATION_COLUMNS,
+            |o| {
+                vec![
+                    o.step.map(|step| step.to_string()).unwrap_or_default(),
+                    o.at.to_string(),
+                    o.command.to_string(),
+                    o.undone.to_string(),
+                ]
+            },
+            format,
+        );
+    }
+    if operations.is_empty() {
+        println!("No history yet.");
+    }
+    for record in records {
+        let step = record
+            .step
+            .map_or_else(|| "undone".to_string(), |step| step.to_string());
+        println!("{:>6}  {}  {}", step, record.at, record.command);
+    }
+}
+
+#[derive(Serialize)]
+struct TrashRecord<'a> {
+    id: usize,
+    deleted_at: &'a str,
+    deleted_by: &'a str,
+    list: &'a str,
+    items: Vec<ItemRecord<'a>>,
+}
+
+const TRASH_COLUMNS: &[&str] = &["id", "deleted_at", "deleted_by", "list", "items"];
+
+pub fn print_trash(
+    groups: &[(String, Vec<Entry>)],
+    by: GroupBy,
+    format: Format,
+    painter: &Painter,
+) {
+    // An entry is listed once, even when it is in several tag groups.
+    let mut seen = HashSet::new();
+    let entries: Vec<&Entry> = groups
+        .iter()
+        .flat_map(|(_, entries)| entries)
+        .filter(|entry| seen.insert(entry.trash.id))
+        .collect();
+    if format != Format::Text {
+        let records: Vec<TrashRecord> = entries
+            .iter()
+            .map(|entry| TrashRecord {
+                id: entry.trash.id,
+                deleted_at: &entry.trash.deleted_at,
+                deleted_by: &entry.trash.deleted_by,
+                list: entry.list_name,
+                items: entry
+                    .items
+                    .iter()
+                    .map(|todo| {
+                        ItemRecord::from(&Row {
+                            list_name: entry.list_name,
+                            todo,
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        return print_records(
+            &records,
+            "trash",
+            TRASH_COLUMNS,
+            |t| {
+                let items: Vec<String> = t.items.iter().map(|i| i.item.to_string()).collect();
+                vec![
+                    t.id.to_string(),
+                    t.deleted_at.to_string(),
+                    t.deleted_by.to_string(),
+                    t.list.to_string(),
+                    items.join(" "),
+                ]
+            },
+            format,
+        );
+    }
+    if entries.is_empty() {
+        println!("The trash is empty.");
+    }
+    for (name, entries) in groups {
+        print_group_header(name, by, painter);
+        for entry in entries {
+            let what = match entry.items.as_slice() {
+                [todo] => format!("{} {}: {}", entry.list_name, todo.item_number, todo.title),
+                items => format!("{} ({} items)", entry.list_name, items.len()),
+            };
+            println!(
+                "{:>4}  {}  {}  {}",
+                entry.trash.id, entry.trash.deleted_at, entry.trash.deleted_by, what
+            );
+        }
+    }
+}
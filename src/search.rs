@@ -0,0 +1,72 @@
+use crate::listing::{self, Row};
+use crate::todo::User;
+use rusqlite::{params, Connection, Result};
+
+// Control characters FTS5 wraps matches in; `mark_matches` swaps them for
+// whatever the output needs.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+#[derive(Clone)]
+pub struct SearchHit<'a> {
+    pub row: Row<'a>,
+    pub title: String,
+    pub snippet: String,
+}
+
+/// Runs an FTS5 query (prefix `cert*`, phrase `"tls cert"`, `AND`/`OR`/`NOT`)
+/// over the user's items as they are now, unpushed changes and lists shared
+/// with them included, or with `archived` their archived ones, best match
+/// first. `snippet` is the matching part of the notes, if any.
+///
+/// Matches in `title` and `snippet` are delimited by markers; render them
+/// with [`mark_matches`].
+pub fn search<'a>(user: &'a User, query: &str, archived: bool) -> Result<Vec<SearchHit<'a>>> {
+    let rows = listing::rows(user, None, archived);
+    // The items are indexed in a throwaway table so the query language and
+    // ranking are the same FTS5 ones the database uses.
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch("CREATE VIRTUAL TABLE items USING fts5(title, notes, tags);")?;
+    let mut insert =
+        conn.prepare("INSERT INTO items (rowid, title, notes, tags) VALUES (?1, ?2, ?3, ?4)")?;
+    for (index, row) in rows.iter().enumerate() {
+        insert.execute(params![
+            index as i64,
+            row.todo.title,
+            row.todo.notes,
+            row.todo.tags.join(" ")
+        ])?;
+    }
+    let mut stmt = conn.prepare(
+        "SELECT rowid, highlight(items, 0, char(2), char(3)),
+            snippet(items, 1, char(2), char(3), '...', 8)
+        FROM items WHERE items MATCH ?1 ORDER BY rank",
+    )?;
+    let hits = stmt.query_map(params![query], |row| {
+        Ok(SearchHit {
+            row: rows[row.get::<_, i64>(0)? as usize],
+            title: row.get(1)?,
+            snippet: row.get(2)?,
+        })
+    })?;
+    hits.collect()
+}
+
+/// Wraps each match in `text` with the strings returned by `mark`.
+pub fn mark_matches(text: &str, mark: impl Fn(&str) -> String) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(MATCH_START) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + MATCH_START.len_utf8()..];
+        let end = rest.find(MATCH_END).unwrap_or(rest.len());
+        out.push_str(&mark(&rest[..end]));
+        rest = rest.get(end + MATCH_END.len_utf8()..).unwrap_or("");
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn has_match(text: &str) -> bool {
+    text.contains(MATCH_START)
+}
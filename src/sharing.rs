@@ -0,0 +1,216 @@
+//! Sharing lists between users. The `list_members` table grants other users
+//! a role on one of an owner's lists; the owner's own access is implied by
+//! the items being theirs. Members see the list as `<owner>/<list>`.
+
+use crate::notifications;
+use clap::ValueEnum;
+use rusqlite::{ffi, params, Connection, Error, OptionalExtension, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Role {
+    /// Can edit the list and share it with others.
+    Owner,
+    /// Can add, change and remove items.
+    Editor,
+    /// Can only look.
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Role> {
+        Role::value_variants()
+            .iter()
+            .copied()
+            .find(|role| role.as_str() == text)
+    }
+
+    pub fn can_edit(self) -> bool {
+        self != Role::Viewer
+    }
+
+    /// Whether the role may share the list, unshare it or remove it whole.
+    pub fn can_manage(self) -> bool {
+        self == Role::Owner
+    }
+}
+
+/// Someone else's list as a member sees it.
+#[derive(Clone)]
+pub struct Shared {
+    pub owner: String,
+    pub list_name: String,
+    pub role: Role,
+}
+
+/// The name members see a shared list under.
+pub fn shared_name(owner: &str, list_name: &str) -> String {
+    format!("{}/{}", owner, list_name)
+}
+
+/// The name `user_name` sees `owner`'s list under.
+pub fn seen_as(owner: &str, list_name: &str, user_name: &str) -> String {
+    if user_name == owner {
+        list_name.to_string()
+    } else {
+        shared_name(owner, list_name)
+    }
+}
+
+pub struct Member {
+    pub user_name: String,
+    pub email: String,
+    pub role: Role,
+}
+
+/// The error for a change the user's role doesn't allow.
+pub fn denied(message: String) -> Error {
+    Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_PERM), Some(message))
+}
+
+fn user_id(conn: &Connection, user_name: &str) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM users WHERE user_name = ?1",
+        params![user_name],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Gives the user with `email` a role on `owner`'s list, replacing any role
+/// they had, and lets them know. Returns their user name, or `None` if nobody
+/// has that email. The owner must already be in the database.
+pub fn share(
+    conn: &Connection,
+    owner: &str,
+    list_name: &str,
+    email: &str,
+    role: Role,
+) -> Result<Option<String>> {
+    let Some(member) = conn
+        .query_row(
+            "SELECT id, user_name FROM users WHERE email = ?1 ORDER BY id LIMIT 1",
+            params![email],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let added = conn.execute(
+        "INSERT INTO list_members (owner_id, list_name, member_id, role)
+        SELECT id, ?2, ?3, ?4 FROM users WHERE user_name = ?1 AND id != ?3
+        ON CONFLICT (owner_id, list_name, member_id) DO UPDATE SET role = excluded.role",
+        params![owner, list_name, member.0, role.as_str()],
+    )?;
+    if added > 0 {
+        notifications::notify(
+            conn,
+            &member.1,
+            "share",
+            &format!(
+                "{} shared {} with you as {}.",
+                owner,
+                list_name,
+                role.as_str()
+            ),
+        )?;
+    }
+    Ok(Some(member.1))
+}
+
+/// Takes away the role of the user with `email` and lets them know. Returns
+/// whether they had one.
+pub fn unshare(conn: &Connection, owner: &str, list_name: &str, email: &str) -> Result<bool> {
+    let removed: Vec<String> = members(conn, owner, list_name)?
+        .into_iter()
+        .filter(|member| member.email == email)
+        .map(|member| member.user_name)
+        .collect();
+    conn.execute(
+        "DELETE FROM list_members
+        WHERE owner_id = (SELECT id FROM users WHERE user_name = ?1) AND list_name = ?2
+            AND member_id IN (SELECT id FROM users WHERE email = ?3)",
+        params![owner, list_name, email],
+    )?;
+    for member in &removed {
+        notifications::notify(
+            conn,
+            member,
+            "unshare",
+            &format!("{} stopped sharing {} with you.", owner, list_name),
+        )?;
+    }
+    Ok(!removed.is_empty())
+}
+
+pub fn members(conn: &Connection, owner: &str, list_name: &str) -> Result<Vec<Member>> {
+    let mut stmt = conn.prepare(
+        "SELECT u.user_name, u.email, m.role FROM list_members m
+        JOIN users u ON u.id = m.member_id
+        JOIN users o ON o.id = m.owner_id
+        WHERE o.user_name = ?1 AND m.list_name = ?2
+        ORDER BY u.user_name",
+    )?;
+    let members = stmt.query_map(params![owner, list_name], |row| {
+        Ok(Member {
+            user_name: row.get(0)?,
+            email: row.get(1)?,
+            role: Role::parse(&row.get::<_, String>(2)?).unwrap_or(Role::Viewer),
+        })
+    })?;
+    members.collect()
+}
+
+/// The lists other users have shared with `user_name`.
+pub fn shared_with(conn: &Connection, user_name: &str) -> Result<Vec<Shared>> {
+    let mut stmt = conn.prepare(
+        "SELECT o.user_name, m.list_name, m.role FROM list_members m
+        JOIN users o ON o.id = m.owner_id
+        JOIN users u ON u.id = m.member_id
+        WHERE u.user_name = ?1
+        ORDER BY o.user_name, m.list_name",
+    )?;
+    let shared = stmt.query_map(params![user_name], |row| {
+        Ok(Shared {
+            owner: row.get(0)?,
+            list_name: row.get(1)?,
+            role: Role::parse(&row.get::<_, String>(2)?).unwrap_or(Role::Viewer),
+        })
+    })?;
+    shared.collect()
+}
+
+/// Whether `user_name` can see `owner`'s list: they own it or it is shared
+/// with them.
+pub fn has_access(
+    conn: &Connection,
+    owner: &str,
+    list_name: &str,
+    user_name: &str,
+) -> Result<bool> {
+    Ok(user_name == owner || role(conn, owner, list_name, user_name)?.is_some())
+}
+
+/// The role `member` has on `owner`'s list right now, if any.
+pub fn role(conn: &Connection, owner: &str, list_name: &str, member: &str) -> Result<Option<Role>> {
+    let (Some(owner_id), Some(member_id)) = (user_id(conn, owner)?, user_id(conn, member)?) else {
+        return Ok(None);
+    };
+    let role: Option<String> = conn
+        .query_row(
+            "SELECT role FROM list_members
+            WHERE owner_id = ?1 AND list_name = ?2 AND member_id = ?3",
+            params![owner_id, list_name, member_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(role.as_deref().and_then(Role::parse))
+}
@@ -0,0 +1,185 @@
+//! Productivity statistics for `todo stats`, worked out from the creation and
+//! completion times of the user's items. Items in the trash are left out;
+//! archived ones count, since they are finished work.
+
+use crate::todo::{TodoItem, User};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The first day of the period `date` falls in; weeks start on Monday.
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            Period::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    fn previous(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start - Duration::days(1),
+            Period::Week => start - Duration::days(7),
+            Period::Month => start - Months::new(1),
+        }
+    }
+
+    /// `2024-03-05`, `2024-W10` (ISO week) or `2024-03`.
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => start.format("%G-W%V").to_string(),
+            Period::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PeriodCount {
+    pub period: String,
+    pub created: usize,
+    pub completed: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ListRate {
+    pub list: String,
+    pub completed: usize,
+    pub total: usize,
+    /// Completed over total, from 0 to 1.
+    pub rate: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OpenItem {
+    pub list: String,
+    pub item: usize,
+    pub title: String,
+    pub created: String,
+    pub age_days: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Stats {
+    /// Oldest first, ending with the current period.
+    pub periods: Vec<PeriodCount>,
+    pub lists: Vec<ListRate>,
+    /// Mean time from creation to completion, for items with a completion time.
+    pub average_days_to_complete: Option<f64>,
+    /// Oldest first.
+    pub oldest_open: Vec<OpenItem>,
+}
+
+fn parse_time(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").ok()
+}
+
+/// Works out the stats as of `now`, over the last `periods` periods and with
+/// up to `oldest` open items.
+pub fn stats(
+    user: &User,
+    period: Period,
+    periods: usize,
+    oldest: usize,
+    now: NaiveDateTime,
+) -> Stats {
+    let items: Vec<(&String, &TodoItem)> = user
+        .todo_lists
+        .iter()
+        .flat_map(|(name, list)| list.todos.iter().map(move |todo| (name, todo)))
+        .filter(|(_, todo)| !todo.is_deleted)
+        .collect();
+
+    let mut starts = vec![period.start(now.date())];
+    while starts.len() < periods {
+        starts.push(period.previous(*starts.last().unwrap()));
+    }
+    starts.reverse();
+    let periods = starts
+        .iter()
+        .map(|&start| {
+            let within = |time: Option<&str>| {
+                time.and_then(parse_time)
+                    .is_some_and(|time| period.start(time.date()) == start)
+            };
+            PeriodCount {
+                period: period.label(start),
+                created: items
+                    .iter()
+                    .filter(|(_, todo)| within(Some(&todo.datetime)))
+                    .count(),
+                completed: items
+                    .iter()
+                    .filter(|(_, todo)| todo.is_completed)
+                    .filter(|(_, todo)| within(todo.completed_at.as_deref()))
+                    .count(),
+            }
+        })
+        .collect();
+
+    let mut lists: Vec<ListRate> = Vec::new();
+    for (name, todo) in &items {
+        let index = match lists.iter().position(|list| &list.list == *name) {
+            Some(index) => index,
+            None => {
+                lists.push(ListRate {
+                    list: name.to_string(),
+                    completed: 0,
+                    total: 0,
+                    rate: 0.0,
+                });
+                lists.len() - 1
+            }
+        };
+        lists[index].total += 1;
+        lists[index].completed += todo.is_completed as usize;
+    }
+    for list in &mut lists {
+        list.rate = list.completed as f64 / list.total as f64;
+    }
+    lists.sort_by(|a, b| a.list.cmp(&b.list));
+
+    let ages: Vec<f64> = items
+        .iter()
+        .filter(|(_, todo)| todo.is_completed)
+        .filter_map(|(_, todo)| {
+            let created = parse_time(&todo.datetime)?;
+            let completed = parse_time(todo.completed_at.as_deref()?)?;
+            Some((completed - created).num_seconds() as f64 / 86400.0)
+        })
+        .collect();
+    let average_days_to_complete =
+        (!ages.is_empty()).then(|| ages.iter().sum::<f64>() / ages.len() as f64);
+
+    let mut open: Vec<&(&String, &TodoItem)> = items
+        .iter()
+        .filter(|(_, todo)| !todo.is_completed && todo.archived_at.is_none())
+        .collect();
+    open.sort_by(|a, b| a.1.datetime.cmp(&b.1.datetime));
+    let oldest_open = open
+        .into_iter()
+        .take(oldest)
+        .map(|(name, todo)| OpenItem {
+            list: name.to_string(),
+            item: todo.item_number,
+            title: todo.title.clone(),
+            created: todo.datetime.clone(),
+            age_days: parse_time(&todo.datetime).map_or(0, |created| (now - created).num_days()),
+        })
+        .collect();
+
+    Stats {
+        periods,
+        lists,
+        average_days_to_complete,
+        oldest_open,
+    }
+}
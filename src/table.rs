@@ -0,0 +1,74 @@
+use terminal_size::{terminal_size, Width};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const GAP: &str = "  ";
+const MIN_WIDTH: usize = 4;
+
+/// Width of the terminal on stdout, if stdout is a terminal.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size().map(|(Width(width), _)| width as usize)
+}
+
+/// Cuts `text` to at most `width` display columns, ending in `…` when cut.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+/// Display width of each column, shrinking the widest columns until a row
+/// fits in `max_width`.
+pub fn column_widths(
+    headers: &[&str],
+    rows: &[Vec<String>],
+    max_width: Option<usize>,
+) -> Vec<usize> {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.lines().next().unwrap_or("").width());
+        }
+    }
+    if let Some(max_width) = max_width {
+        let gaps = GAP.len() * widths.len().saturating_sub(1);
+        while widths.iter().sum::<usize>() + gaps > max_width {
+            let widest = widths.iter_mut().max().unwrap();
+            if *widest <= MIN_WIDTH {
+                break;
+            }
+            *widest -= 1;
+        }
+    }
+    widths
+}
+
+/// Lays out one row; multi-line cells show only their first line.
+pub fn format_row<S: AsRef<str>>(cells: &[S], widths: &[usize]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| {
+            pad(
+                &truncate(cell.as_ref().lines().next().unwrap_or(""), width),
+                width,
+            )
+        })
+        .collect();
+    cells.join(GAP).trim_end().to_string()
+}
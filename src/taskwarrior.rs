@@ -0,0 +1,307 @@
+//! [Taskwarrior](https://taskwarrior.org) JSON, as written by `task export`
+//! and read by `task import`.
+//!
+//! `project` is the list, `H`/`M`/`L` priorities map to A/B/C, `due` is the
+//! due date and each annotation is a line of the item's notes. Tasks are
+//! matched to items by `uuid`. Attributes items have no field for (`depends`,
+//! `wait`, UDAs, ...) are kept in the item's `extra` map and written back on
+//! export, and so are the parts of known ones that items can't hold: the
+//! annotations' timestamps, tags with spaces and other priority values.
+//! Priorities D-Z are exported as `L` with the letter in `todo_priority`.
+
+use crate::exchange::{adopt_uid, Foreign, ImportReport};
+use crate::ics::{self, from_ical, to_utc};
+use crate::todo::{TodoItem, User};
+use chrono::{Duration, NaiveDateTime};
+use serde_json::{json, Map, Value};
+
+/// Attributes that map onto item fields, or that Taskwarrior recomputes.
+const KNOWN: &[&str] = &[
+    "uuid",
+    "description",
+    "status",
+    "entry",
+    "end",
+    "due",
+    "project",
+    "tags",
+    "priority",
+    PRIORITY_KEY,
+    "annotations",
+    "id",
+    "urgency",
+];
+
+/// The attribute holding priorities Taskwarrior has no value for; Taskwarrior
+/// keeps it like any other unknown attribute.
+const PRIORITY_KEY: &str = "todo_priority";
+
+/// Tags as items hold them: without spaces.
+fn tag_name(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+fn text<'a>(task: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    task.get(key).and_then(Value::as_str)
+}
+
+fn date(task: &Map<String, Value>, key: &str) -> Result<Option<String>, String> {
+    match text(task, key) {
+        Some(value) => from_ical(value)
+            .map(Some)
+            .ok_or_else(|| format!("invalid {} '{}'", key, value)),
+        None => Ok(None),
+    }
+}
+
+fn apply(todo: &mut TodoItem, task: &Map<String, Value>) -> Result<(), String> {
+    let title = text(task, "description").unwrap_or_default().trim();
+    if title.is_empty() {
+        return Err("missing description".to_string());
+    }
+    let created = date(task, "entry")?;
+    let completed_at = date(task, "end")?;
+    let due = date(task, "due")?;
+    let priority = match (text(task, PRIORITY_KEY), text(task, "priority")) {
+        (Some(letter), _) => match letter.chars().next() {
+            Some(c @ 'A'..='Z') if letter.len() == 1 => Some(c),
+            _ => return Err(format!("invalid {} '{}'", PRIORITY_KEY, letter)),
+        },
+        (None, Some("H")) => Some('A'),
+        (None, Some("M")) => Some('B'),
+        (None, Some("L")) => Some('C'),
+        (None, _) => None,
+    };
+    let mut notes = Vec::new();
+    for annotation in task
+        .get("annotations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        match annotation.get("description").and_then(Value::as_str) {
+            Some(description) => notes.push(description),
+            None => return Err("annotation without a description".to_string()),
+        }
+    }
+
+    if let Some(created) = created {
+        todo.datetime = created;
+    }
+    todo.title = title.to_string();
+    todo.is_completed = text(task, "status") == Some("completed");
+    todo.completed_at = completed_at.filter(|_| todo.is_completed);
+    todo.priority = priority;
+    todo.due = due;
+    let tags: Vec<&str> = task
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    todo.tags = tags.iter().map(|tag| tag_name(tag)).collect();
+    todo.notes = notes.join("\n");
+    todo.extra = task
+        .iter()
+        .filter(|(key, _)| !KNOWN.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if tags.iter().any(|tag| tag_name(tag) != *tag) {
+        todo.extra.insert("tags".to_string(), json!(tags));
+    }
+    if let Some(annotations) = task.get("annotations") {
+        todo.extra
+            .insert("annotations".to_string(), annotations.clone());
+    }
+    if priority.is_none() {
+        if let Some(value) = task.get("priority") {
+            todo.extra.insert("priority".to_string(), value.clone());
+        }
+    }
+    // `waiting` and `recurring` tasks are open items that keep their status.
+    if let Some(status) = text(task, "status").filter(|s| !["pending", "completed"].contains(s)) {
+        todo.extra.insert("status".to_string(), json!(status));
+    }
+    todo.is_modified = true;
+    Ok(())
+}
+
+fn import_task(
+    user: &mut User,
+    task: &Map<String, Value>,
+    default_list: &str,
+    foreign: &Foreign,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    if text(task, "status") == Some("deleted") {
+        return Err("task is deleted".to_string());
+    }
+    let uid = text(task, "uuid").filter(|uid| !uid.is_empty());
+    if let Some(todo) = uid.and_then(|uid| user.find_live(uid)) {
+        apply(todo, task)?;
+        report.updated += 1;
+        return Ok(());
+    }
+    let mut todo = TodoItem::new(0, String::new());
+    if let Some(uid) = uid {
+        adopt_uid(user, &mut todo, uid, foreign);
+    }
+    apply(&mut todo, task)?;
+    let list_name = text(task, "project").unwrap_or(default_list);
+    user.get_or_add_todo_list(list_name).add_item(todo);
+    report.imported += 1;
+    Ok(())
+}
+
+/// Reads the tasks with the line each starts on. `task export` writes one task
+/// per line; other JSON arrays are numbered by their position instead.
+fn parse_tasks(text: &str) -> Result<Vec<(usize, Value)>, (usize, String)> {
+    let per_line: Option<Vec<(usize, Value)>> = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let line = line.trim().trim_start_matches('[').trim_end_matches(']');
+            (index + 1, line.trim().trim_end_matches(','))
+        })
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line_number, line)| {
+            serde_json::from_str(line)
+                .ok()
+                .map(|task| (line_number, task))
+        })
+        .collect();
+    if let Some(tasks) = per_line {
+        return Ok(tasks);
+    }
+    match serde_json::from_str::<Vec<Value>>(text) {
+        Ok(tasks) => Ok(tasks
+            .into_iter()
+            .enumerate()
+            .map(|(index, task)| (index + 1, task))
+            .collect()),
+        Err(e) => Err((e.line(), e.to_string())),
+    }
+}
+
+pub fn import(user: &mut User, text: &str, default_list: &str, foreign: &Foreign) -> ImportReport {
+    let mut report = ImportReport::default();
+    match parse_tasks(text) {
+        Ok(tasks) => {
+            for (line_number, task) in tasks {
+                let result = match task.as_object() {
+                    Some(task) => import_task(user, task, default_list, foreign, &mut report),
+                    None => Err("expected a JSON object".to_string()),
+                };
+                if let Err(e) = result {
+                    report.errors.push((line_number, e));
+                }
+            }
+        }
+        Err(error) => report.errors.push(error),
+    }
+    report
+}
+
+pub fn format_item(list_name: &str, todo: &TodoItem) -> Value {
+    let mut task = todo.extra.clone();
+    task.remove(ics::EXTRA_KEY);
+    let kept_tags = task.remove("tags");
+    let kept_annotations = task.remove("annotations");
+    let status = match (todo.is_completed, task.remove("status")) {
+        (true, _) => json!("completed"),
+        (false, Some(status)) => status,
+        (false, None) => json!("pending"),
+    };
+    task.insert("uuid".to_string(), json!(todo.uid));
+    task.insert("description".to_string(), json!(todo.title));
+    task.insert("status".to_string(), status);
+    task.insert("project".to_string(), json!(list_name));
+    if let Some(entry) = to_utc(&todo.datetime) {
+        task.insert("entry".to_string(), json!(entry));
+    }
+    if let Some(end) = todo.completed_at.as_deref().and_then(to_utc) {
+        task.insert("end".to_string(), json!(end));
+    }
+    if let Some(due) = todo.due.as_deref().and_then(|due| match due.len() {
+        10 => to_utc(&format!("{} 00:00:00", due)),
+        _ => to_utc(due),
+    }) {
+        task.insert("due".to_string(), json!(due));
+    }
+    if let Some(priority) = todo.priority {
+        let value = match priority {
+            'A' => "H",
+            'B' => "M",
+            _ => "L",
+        };
+        task.insert("priority".to_string(), json!(value));
+        if priority > 'C' {
+            task.insert(PRIORITY_KEY.to_string(), json!(priority.to_string()));
+        }
+    }
+    if !todo.tags.is_empty() {
+        // Write tags with spaces back as long as the item's tags still match.
+        let kept = kept_tags.filter(|kept| {
+            let kept: Vec<String> = kept
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(tag_name)
+                .collect();
+            kept == todo.tags
+        });
+        task.insert("tags".to_string(), kept.unwrap_or_else(|| json!(todo.tags)));
+    }
+    // Annotations still in the notes keep their timestamps. Taskwarrior keys
+    // annotations by their timestamp, so new ones each need their own.
+    let mut kept: Vec<&Map<String, Value>> = kept_annotations
+        .as_ref()
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object)
+        .collect();
+    let created = NaiveDateTime::parse_from_str(&todo.datetime, "%Y-%m-%d %H:%M:%S").ok();
+    let annotations: Vec<Value> = todo
+        .notes
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            let found = kept
+                .iter()
+                .position(|kept| kept.get("description").and_then(Value::as_str) == Some(line));
+            if let Some(found) = found {
+                return Value::Object(kept.remove(found).clone());
+            }
+            let entry = created
+                .map(|created| created + Duration::seconds(index as i64))
+                .and_then(|at| to_utc(&at.format("%Y-%m-%d %H:%M:%S").to_string()));
+            json!({ "entry": entry, "description": line })
+        })
+        .collect();
+    if !annotations.is_empty() {
+        task.insert("annotations".to_string(), Value::Array(annotations));
+    }
+    Value::Object(task)
+}
+
+/// Writes a JSON array with one task per line, like `task export`.
+pub fn export(user: &User, lists: &[&str]) -> String {
+    let mut tasks = Vec::new();
+    for list_name in lists {
+        for todo in user.todo_lists[*list_name]
+            .todos
+            .iter()
+            .filter(|t| !t.is_deleted)
+        {
+            tasks.push(format_item(list_name, todo).to_string());
+        }
+    }
+    if tasks.is_empty() {
+        return "[\n]\n".to_string();
+    }
+    format!("[\n{}\n]\n", tasks.join(",\n"))
+}
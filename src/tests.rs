@@ -1,34 +1,43 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
 
-    use crate::todo::User;
-    use chrono::Local;
+    use crate::archive;
+    use crate::backup;
+    use crate::chart;
+    use crate::checklist;
+    use crate::cli::{split_args, Cli, Commands};
+    use crate::color::{parse_style, ColorChoice, Painter, Theme};
+    use crate::comments;
+    use crate::config;
+    use crate::db::{migrate, SCHEMA_VERSION};
+    use crate::dump;
+    use crate::events;
+    use crate::exchange::{self, Foreign};
+    use crate::filter;
+    use crate::focus;
+    use crate::ics;
+    use crate::journal::{self, Journal};
+    use crate::listing::{self, parse_sort_key, GroupBy};
+    use crate::notifications;
+    use crate::output::{csv_field, tsv_field, ItemRecord};
+    use crate::search::{mark_matches, search};
+    use crate::sharing::{self, Role};
+    use crate::stats::{self, Period};
+    use crate::table;
+    use crate::taskwarrior;
+    use crate::timesheet;
+    use crate::todo::{TodoItem, User};
+    use crate::todotxt;
+    use crate::trash;
+    use chrono::{Local, NaiveDate, NaiveDateTime};
+    use clap::Parser;
 
     use rusqlite::{params, Connection, Result};
 
     fn setup_db() -> Result<Connection> {
         let conn = Connection::open_in_memory()?;
-        conn.execute(
-            "CREATE TABLE users (
-                id INTEGER PRIMARY KEY,
-                user_name TEXT NOT NULL UNIQUE,
-                email TEXT NOT NULL
-            )",
-            [],
-        )?;
-        conn.execute(
-            "CREATE TABLE todos (
-                id INTEGER PRIMARY KEY,
-                user_id INTEGER NOT NULL,
-                list_name TEXT NOT NULL,
-                item_number INTEGER NOT NULL,
-                title TEXT NOT NULL,
-                datetime TEXT NOT NULL,
-                is_completed INTEGER NOT NULL,
-                FOREIGN KEY(user_id) REFERENCES users(id)
-            )",
-            [],
-        )?;
+        migrate(&conn)?;
         Ok(conn)
     }
 
@@ -115,7 +124,7 @@ mod tests {
         let todo_list = user.get_todo_list(&"Work".to_string()).unwrap();
 
         todo_list.add("Complete report".to_string());
-        user.push_to_db().unwrap();
+        user.push_to_db(&conn).unwrap();
 
         let mut stmt = conn
             .prepare("SELECT user_name, email FROM users WHERE user_name = ?1")
@@ -152,7 +161,7 @@ mod tests {
             params![user_id, "Work", 1, "Complete report", get_current_date_time(), 0],
         ).unwrap();
 
-        let mut user = User::pull_from_db(&user_name).unwrap();
+        let mut user = User::pull_from_db(&conn, &user_name).unwrap();
         assert_eq!(user.user_name, "test_user");
         assert_eq!(user.email, "test_email@example.com");
         assert!(user.todo_lists.contains_key("Work"));
@@ -166,4 +175,1769 @@ mod tests {
         let local_now = Local::now();
         local_now.format("%Y-%m-%d %H:%M:%S").to_string()
     }
+
+    #[test]
+    fn test_notes_round_trip() {
+        let conn = setup_db().unwrap();
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.add_todo_list("Work".to_string());
+        let todo_list = user.get_todo_list(&"Work".to_string()).unwrap();
+
+        todo_list.add("Renew TLS cert".to_string());
+        todo_list.append_note(1, "Expires on the 3rd.");
+        todo_list.append_note(1, "Ticket: OPS-42");
+        assert_eq!(
+            todo_list.todos[0].notes,
+            "Expires on the 3rd.\nTicket: OPS-42"
+        );
+        user.push_to_db(&conn).unwrap();
+
+        let mut user = User::pull_from_db(&conn, "test_user").unwrap();
+        let todo_list = user.get_todo_list(&"Work".to_string()).unwrap();
+        assert_eq!(
+            todo_list.todos[0].notes,
+            "Expires on the 3rd.\nTicket: OPS-42"
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.add_todo_list("Ops".to_string());
+        let todo_list = user.get_todo_list(&"Ops".to_string()).unwrap();
+        todo_list.add("Renew TLS certificate".to_string());
+        todo_list.add("Rotate database passwords".to_string());
+        todo_list.append_note(2, "Coordinate with the TLS rollout");
+
+        // Unpushed items are searched.
+        let hits = search(&user, "certif*", false).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].row.list_name, "Ops");
+        assert_eq!(hits[0].row.todo.item_number, 1);
+        assert_eq!(
+            mark_matches(&hits[0].title, |m| format!("[{}]", m)),
+            "Renew TLS [certificate]"
+        );
+
+        let hits = search(&user, "tls NOT certificate", false).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].row.todo.item_number, 2);
+        assert!(mark_matches(&hits[0].snippet, |m| format!("<{}>", m)).contains("<TLS>"));
+
+        let todo_list = user.get_todo_list(&"Ops".to_string()).unwrap();
+        todo_list.set_notes(2, String::new());
+        assert!(search(&user, "tls NOT certificate", false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search_shared_lists() -> Result<()> {
+        let conn = setup_db()?;
+        let mut bob = User::new("bob".to_string(), "bob@example.com".to_string());
+        bob.push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        alice
+            .get_or_add_todo_list("Work")
+            .add("Renew TLS certificate".to_string());
+        alice.push_to_db(&conn)?;
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Viewer)?;
+
+        let bob = User::pull_from_db(&conn, "bob")?;
+        let hits = search(&bob, "certificate", false)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].row.list_name, "alice/Work");
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_parse_and_match() {
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.add_todo_list("Work".to_string());
+        let todo_list = user.get_todo_list(&"Work".to_string()).unwrap();
+        todo_list.add("Renew TLS cert".to_string());
+        todo_list.add("Write report".to_string());
+        todo_list.mark_complete(2);
+        todo_list.todos[0].datetime = "2024-01-15 09:00:00".to_string();
+
+        let todos = &todo_list.todos;
+        let filter = filter::parse("list:work and not done and (title:tls or item>=3)").unwrap();
+        assert!(filter.matches("Work", &todos[0]));
+        assert!(!filter.matches("Work", &todos[1]));
+        assert!(!filter.matches("Home", &todos[0]));
+
+        let filter = filter::parse("done or created<2024-02-01").unwrap();
+        assert!(filter.matches("Work", &todos[0]));
+        assert!(filter.matches("Work", &todos[1]));
+
+        let filter = filter::parse("report").unwrap();
+        assert!(!filter.matches("Work", &todos[0]));
+        assert!(filter.matches("Work", &todos[1]));
+
+        // Items without a due date never match a due comparison.
+        todo_list.set_due(1, Some(filter::parse_due("+3d").unwrap()));
+        let todos = &todo_list.todos;
+        let filter = filter::parse("due<+7d").unwrap();
+        assert!(filter.matches("Work", &todos[0]));
+        assert!(!filter.matches("Work", &todos[1]));
+        assert!(!filter::parse("due<today")
+            .unwrap()
+            .matches("Work", &todos[0]));
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        let err = filter::parse("list:work and colour:red").unwrap_err();
+        assert_eq!(err.position, 14);
+        assert!(err.message.starts_with("unknown field 'colour'"));
+
+        let err = filter::parse("(done or list:work").unwrap_err();
+        assert_eq!(err.message, "expected ')'");
+        assert_eq!(err.position, 18);
+
+        assert!(filter::parse("item>=three").is_err());
+        assert!(filter::parse("title>report").is_err());
+        assert!(filter::parse("done list:work").is_err());
+        // Dates that can't be worked out are rejected rather than panicking.
+        assert!(filter::parse("created>+é").is_err());
+        assert!(filter::parse("created<+100000000d").is_err());
+        assert!(filter::parse("created<-9223372036854775807w").is_err());
+        assert!(filter::parse("created<--9223372036854775808d").is_err());
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("todo show 'list:work and not done'").unwrap(),
+            vec!["todo", "show", "list:work and not done"]
+        );
+        assert_eq!(
+            split_args("todo add \"Grocery List\" milk").unwrap(),
+            vec!["todo", "add", "Grocery List", "milk"]
+        );
+        assert!(split_args("todo show 'oops").is_err());
+
+        // A dump named like a number is still a dump, and only trash entries are undoable.
+        let parse = |line: &str| Cli::try_parse_from(split_args(line).unwrap()).unwrap();
+        assert!(matches!(
+            parse("todo restore 20240101").command,
+            Commands::Restore { ref file, merge: false } if file.to_str() == Some("20240101")
+        ));
+        assert!(!parse("todo restore 20240101 --merge").command.is_mutating());
+        assert!(parse("todo trash restore 3").command.is_mutating());
+        assert!(Cli::try_parse_from(["todo", "trash", "restore", "3", "--merge"]).is_err());
+    }
+
+    #[test]
+    fn test_sort_and_group() {
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        for list_name in ["work", "Home", "Errands"] {
+            user.add_todo_list(list_name.to_string());
+        }
+        let home = user.get_todo_list(&"Home".to_string()).unwrap();
+        home.add("mow lawn".to_string());
+        home.add("Fix sink".to_string());
+        home.mark_complete(2);
+        let work = user.get_todo_list(&"work".to_string()).unwrap();
+        work.add("Budget".to_string());
+        work.add("Archive logs".to_string());
+        work.remove_item(2);
+        work.todos[0].tags = vec!["money".to_string(), "Admin".to_string()];
+        let home = user.get_todo_list(&"Home".to_string()).unwrap();
+        home.todos[1].tags = vec!["admin".to_string()];
+
+        let rows = listing::rows(&user, None, false);
+        let order: Vec<_> = rows
+            .iter()
+            .map(|row| (row.list_name, row.todo.item_number))
+            .collect();
+        assert_eq!(order, vec![("Home", 1), ("Home", 2), ("work", 1)]);
+
+        let mut rows = listing::rows(&user, None, false);
+        let keys = vec![
+            parse_sort_key("status:desc").unwrap(),
+            parse_sort_key("title").unwrap(),
+        ];
+        listing::sort_rows(&mut rows, &keys);
+        let titles: Vec<_> = rows.iter().map(|row| row.todo.title.as_str()).collect();
+        assert_eq!(titles, vec!["Fix sink", "Budget", "mow lawn"]);
+
+        let groups = listing::group_rows(rows, GroupBy::Status);
+        let names: Vec<_> = groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Open", "Done"]);
+        assert_eq!(groups[0].1.len(), 2);
+
+        let groups = listing::group_rows(listing::rows(&user, None, false), GroupBy::Tag);
+        let titles: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(name, rows)| {
+                (
+                    name.as_str(),
+                    rows.iter().map(|row| row.todo.title.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                ("admin", vec!["Fix sink"]),
+                ("Admin", vec!["Budget"]),
+                ("money", vec!["Budget"]),
+                ("", vec!["mow lawn"]),
+            ]
+        );
+
+        // Items without a due date sort last; finished ones are never overdue.
+        let day = |offset: i64| {
+            (Local::now().date_naive() + chrono::Duration::days(offset))
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+        let home = user.get_todo_list(&"Home".to_string()).unwrap();
+        assert!(home.set_due(1, Some(day(-1))));
+        assert!(home.set_due(2, Some(day(-2))));
+        let mut rows = listing::rows(&user, None, false);
+        listing::sort_rows(&mut rows, &[parse_sort_key("due").unwrap()]);
+        let titles: Vec<_> = rows.iter().map(|row| row.todo.title.as_str()).collect();
+        assert_eq!(titles, vec!["Fix sink", "mow lawn", "Budget"]);
+        let groups = listing::group_rows(rows, GroupBy::Due);
+        let names: Vec<_> = groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Overdue", "Earlier", "No due date"]);
+        assert!(user.todo_lists["Home"].todos[0].is_overdue());
+
+        // The agenda only has open items with a due date.
+        let work = user.get_todo_list(&"work".to_string()).unwrap();
+        work.set_due(1, Some(day(3)));
+        let agenda: Vec<_> = listing::agenda(&user)
+            .iter()
+            .map(|row| row.todo.title.as_str())
+            .collect();
+        assert_eq!(agenda, vec!["mow lawn", "Budget"]);
+
+        assert!(parse_sort_key("bogus").is_err());
+        assert!(parse_sort_key("title:sideways").is_err());
+    }
+
+    #[test]
+    fn test_output_records() {
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.add_todo_list("Work".to_string());
+        let todo_list = user.get_todo_list(&"Work".to_string()).unwrap();
+        todo_list.add("Write \"report\", v2".to_string());
+        todo_list.todos[0].datetime = "2024-01-15 09:00:00".to_string();
+
+        let row = listing::find(&user, "Work", 1).unwrap();
+        let value = serde_json::to_value(ItemRecord::from(&row)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "list": "Work",
+                "item": 1,
+                "title": "Write \"report\", v2",
+                "created": "2024-01-15 09:00:00",
+                "completed": false,
+                "completed_at": null,
+                "priority": null,
+                "tags": [],
+                "notes": "",
+                "assignee": null,
+                "due": null,
+            })
+        );
+
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(
+            csv_field("Write \"report\", v2"),
+            "\"Write \"\"report\"\", v2\""
+        );
+        assert_eq!(tsv_field("a\tb\nc"), "a\\tb\\nc");
+    }
+
+    #[test]
+    fn test_table_alignment() {
+        assert_eq!(table::truncate("short", 10), "short");
+        assert_eq!(table::truncate("a long title", 6), "a lon…");
+        // Wide characters take two columns each.
+        assert_eq!(table::truncate("日本語のタイトル", 7), "日本語…");
+
+        let headers = ["#", "Title", "Done"];
+        let rows = vec![
+            vec!["1".to_string(), "日本語".to_string(), "✓".to_string()],
+            vec!["2".to_string(), "emoji 🎉".to_string(), String::new()],
+        ];
+        let widths = table::column_widths(&headers, &rows, None);
+        assert_eq!(widths, vec![1, 8, 4]);
+        assert_eq!(table::format_row(&rows[0], &widths), "1  日本語    ✓");
+        assert_eq!(table::format_row(&rows[1], &widths), "2  emoji 🎉");
+
+        let widths = table::column_widths(&headers, &rows, Some(14));
+        assert_eq!(widths, vec![1, 5, 4]);
+        assert_eq!(table::format_row(&rows[1], &widths), "2  emoj…");
+    }
+
+    #[test]
+    fn test_theme_and_painter() {
+        assert_eq!(parse_style("bold red on_black").unwrap(), "1;31;40");
+        assert_eq!(parse_style("dim strikethrough").unwrap(), "2;9");
+        assert!(parse_style("blinking").is_err());
+
+        let config = config::parse("[theme]\ndone = \"bright_green\"\n").unwrap();
+        assert_eq!(config.theme.header, "bold underline");
+        let theme = Theme::from_config(&config.theme).unwrap();
+        assert_eq!(theme.done, "92");
+        assert_eq!(theme.priority_high, "1;31");
+        assert_eq!(theme.overdue, "31");
+        let config = config::parse("[theme]\npriority_high = \"reverse\"\n").unwrap();
+        assert_eq!(
+            Theme::from_config(&config.theme).unwrap().priority_high,
+            "7"
+        );
+        assert!(config::parse("[theme]\nsparkle = \"red\"\n").is_err());
+
+        let painter = Painter::new(ColorChoice::Always, &theme);
+        assert_eq!(painter.paint("x", |t| &t.done), "\x1b[92mx\x1b[0m");
+        let painter = Painter::new(ColorChoice::Never, &theme);
+        assert_eq!(painter.paint("x", |t| &t.done), "x");
+    }
+
+    #[test]
+    fn test_todotxt_round_trip() {
+        let text = "\
+(A) 2024-01-15 Call Mom +Family @phone due:2024-01-20
+x 2024-01-16 2024-01-10 Renew TLS cert +Ops @infra @urgent pri:B
+Water plants
+x 2024-02-30 Impossible date +Ops
+";
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let report = todotxt::import(&mut user, text, "Inbox");
+        assert_eq!(report.imported, 3);
+        assert_eq!(
+            report.errors,
+            vec![(4, "invalid date '2024-02-30'".to_string())]
+        );
+
+        let family = &user.todo_lists["Family"].todos[0];
+        assert_eq!(family.title, "Call Mom");
+        assert_eq!(family.due.as_deref(), Some("2024-01-20"));
+        assert_eq!(family.priority, Some('A'));
+        assert_eq!(family.tags, vec!["phone"]);
+        assert_eq!(family.datetime, "2024-01-15 00:00:00");
+
+        let ops = &user.todo_lists["Ops"].todos[0];
+        assert!(ops.is_completed);
+        assert_eq!(ops.completed_at.as_deref(), Some("2024-01-16 00:00:00"));
+        assert_eq!(ops.priority, Some('B'));
+        assert!(user.todo_lists.contains_key("Inbox"));
+
+        let exported = todotxt::export(&user, &["Family", "Ops"]).unwrap();
+        assert_eq!(
+            exported,
+            "\
+(A) 2024-01-15 +Family Call Mom @phone due:2024-01-20
+x 2024-01-16 2024-01-10 +Ops Renew TLS cert @infra @urgent pri:B
+"
+        );
+        let mut again = User::new("other".to_string(), "other@example.com".to_string());
+        todotxt::import(&mut again, &exported, "Inbox");
+        assert_eq!(todotxt::export(&again, &["Family", "Ops"]), Ok(exported));
+        assert!(todotxt::left_out(&again, &["Family", "Ops"]).is_empty());
+
+        // Fields todo.txt can't hold are reported.
+        let family = &mut user.todo_lists.get_mut("Family").unwrap().todos[0];
+        family.notes = "Ask about the trip".to_string();
+        family.due = Some("2024-01-20 18:00:00".to_string());
+        user.get_or_add_todo_list("Ops").todos[0].notes = "Use the new CA".to_string();
+        assert_eq!(
+            todotxt::left_out(&user, &["Family", "Ops"]),
+            vec!["notes of 2 items", "due times of 1 item"]
+        );
+
+        // `+project` words in titles and spaces in list names survive a round trip.
+        let mut user = User::new("test_user".to_string(), "test@example.com".to_string());
+        user.get_or_add_todo_list("Home Office")
+            .add("Email +Ops about +Family".to_string());
+        let exported = todotxt::export(&user, &["Home Office"]).unwrap();
+        let mut again = User::new("other".to_string(), "other@example.com".to_string());
+        todotxt::import(&mut again, &exported, "Inbox");
+        assert_eq!(
+            again.todo_lists["Home Office"].todos[0].title,
+            "Email +Ops about +Family"
+        );
+        assert_eq!(todotxt::export(&again, &["Home Office"]), Ok(exported));
+
+        // Anything that would read back differently is refused.
+        for title in ["Call @home", "Rename pri:A", "Move due:2024-01-01"] {
+            let mut user = User::new("test_user".to_string(), "test@example.com".to_string());
+            user.get_or_add_todo_list("Chores").add(title.to_string());
+            assert!(todotxt::export(&user, &["Chores"]).is_err());
+        }
+        let mut user = User::new("test_user".to_string(), "test@example.com".to_string());
+        user.get_or_add_todo_list("snake_case")
+            .add("Rename".to_string());
+        assert!(todotxt::export(&user, &["snake_case"]).is_err());
+    }
+
+    #[test]
+    fn test_filter_tags_and_priority() {
+        let (_, urgent) = todotxt::parse_line("(A) Patch servers @infra").unwrap();
+        let (_, later) = todotxt::parse_line("(C) Tidy wiki @docs").unwrap();
+        let (_, none) = todotxt::parse_line("Someday").unwrap();
+
+        let filter = filter::parse("tag:infra or priority>=medium").unwrap();
+        assert!(filter.matches("Ops", &urgent));
+        assert!(!filter.matches("Ops", &later));
+        assert!(!filter.matches("Ops", &none));
+
+        let filter = filter::parse("priority<high").unwrap();
+        assert!(!filter.matches("Ops", &urgent));
+        assert!(filter.matches("Ops", &later));
+        assert!(filter.matches("Ops", &none));
+        assert!(filter::parse("priority:urgent").is_err());
+    }
+
+    #[test]
+    fn test_item_attributes_round_trip() {
+        let conn = setup_db().unwrap();
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        todotxt::import(&mut user, "(B) Patch servers +Ops @infra @linux\n", "Inbox");
+        user.get_todo_list(&"Ops".to_string())
+            .unwrap()
+            .mark_complete(1);
+        user.push_to_db(&conn).unwrap();
+
+        let user = User::pull_from_db(&conn, "test_user").unwrap();
+        let todo = &user.todo_lists["Ops"].todos[0];
+        assert_eq!(todo.priority, Some('B'));
+        assert_eq!(todo.tags, vec!["infra", "linux"]);
+        assert!(todo.completed_at.is_some());
+        assert_eq!(search(&user, "linux", false).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_markdown_checklist() {
+        let text = "\
+# Sprint notes
+
+Loose item first:
+- [ ] Triage inbox
+
+## Release
+- [x] Tag v1.2
+- [ ] Publish crate
+  - Needs the registry token
+  - [ ] Update docs.rs badge
+
+Some prose that is ignored.
+
+## Follow-ups
+- [?] Unclear mark
+- [ ] Write retro
+";
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let report = checklist::import(&mut user, text, "Inbox");
+        assert_eq!(report.imported, 5);
+        assert_eq!(
+            report.errors,
+            vec![(15, "unknown checkbox mark '[?]'".to_string())]
+        );
+        assert_eq!(
+            user.todo_lists["Sprint notes"].todos[0].title,
+            "Triage inbox"
+        );
+
+        let release = &user.todo_lists["Release"].todos;
+        assert_eq!(release.len(), 3);
+        assert!(release[0].is_completed);
+        assert!(release[0].completed_at.is_some());
+        assert_eq!(release[1].notes, "Needs the registry token");
+        assert_eq!(release[2].title, "Update docs.rs badge");
+
+        // A note that looks like a checkbox stays a note.
+        user.todo_lists
+            .get_mut("Release")
+            .unwrap()
+            .append_note(2, "[x] staged on the mirror");
+
+        let exported = checklist::export(&user, &["Release", "Follow-ups"]);
+        assert_eq!(
+            exported,
+            "\
+## Release
+
+- [x] Tag v1.2
+- [ ] Publish crate
+  - Needs the registry token
+  - \\[x] staged on the mirror
+- [ ] Update docs.rs badge
+
+## Follow-ups
+
+- [ ] Write retro
+"
+        );
+        let mut again = User::new("other".to_string(), "other@example.com".to_string());
+        checklist::import(&mut again, &exported, "Inbox");
+        assert_eq!(
+            again.todo_lists["Release"].todos[1].notes,
+            "Needs the registry token\n[x] staged on the mirror"
+        );
+        assert_eq!(
+            checklist::export(&again, &["Release", "Follow-ups"]),
+            exported
+        );
+    }
+
+    #[test]
+    fn test_ics_round_trip_and_update() {
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.add_todo_list("Work, misc".to_string());
+        let list = user.get_todo_list(&"Work, misc".to_string()).unwrap();
+        list.add(
+            "Ship the release; then celebrate with a fairly long title that must be folded"
+                .to_string(),
+        );
+        list.mark_complete(1);
+        list.todos[0].priority = Some('B');
+        list.todos[0].tags = vec!["ops".to_string()];
+        list.todos[0].notes = "line one\nline two".to_string();
+
+        let exported = ics::export(&user, &["Work, misc"]);
+        assert!(exported.contains("CATEGORIES:Work\\, misc,ops\r\n"));
+        assert!(exported.contains("PRIORITY:2\r\n"));
+        assert!(exported.contains("DESCRIPTION:line one\\nline two\r\n"));
+        assert!(exported.lines().all(|line| line.len() <= 75));
+
+        let mut other = User::new("other".to_string(), "other@example.com".to_string());
+        let report = ics::import(&mut other, &exported, "Inbox", &Foreign::new());
+        assert_eq!((report.imported, report.updated), (1, 0));
+        let original = &user.todo_lists["Work, misc"].todos[0];
+        let copy = &other.todo_lists["Work, misc"].todos[0];
+        assert_eq!(copy.uid, original.uid);
+        assert_eq!(copy.title, original.title);
+        assert_eq!(copy.datetime, original.datetime);
+        assert_eq!(copy.completed_at, original.completed_at);
+        assert_eq!(copy.priority, Some('B'));
+        assert_eq!(copy.tags, vec!["ops".to_string()]);
+        assert_eq!(copy.notes, "line one\nline two");
+
+        let edited = exported.replace("STATUS:COMPLETED", "STATUS:NEEDS-ACTION");
+        let edited: String = edited
+            .lines()
+            .filter(|line| !line.starts_with("COMPLETED:"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let report = ics::import(&mut other, &edited, "Inbox", &Foreign::new());
+        assert_eq!((report.imported, report.updated), (0, 1));
+        assert!(!other.todo_lists["Work, misc"].todos[0].is_completed);
+
+        // Properties items have no field for come back out on export.
+        let calendar = "\
+BEGIN:VCALENDAR
+BEGIN:VTODO
+UID:due-1
+SUMMARY:Pay rent
+DUE;VALUE=DATE:20240601
+RRULE:FREQ=MONTHLY
+BEGIN:VALARM
+ACTION:DISPLAY
+TRIGGER:-PT1H
+END:VALARM
+END:VTODO
+END:VCALENDAR
+";
+        let report = ics::import(&mut other, calendar, "Inbox", &Foreign::new());
+        assert_eq!(report.imported, 1);
+        let exported = ics::export(&other, &["Inbox"]);
+        for line in [
+            "DUE;VALUE=DATE:20240601",
+            "RRULE:FREQ=MONTHLY",
+            "BEGIN:VALARM",
+            "TRIGGER:-PT1H",
+            "END:VALARM",
+        ] {
+            assert!(exported.contains(&format!("{}\r\n", line)), "{}", line);
+        }
+        assert!(exported.find("END:VALARM") < exported.find("END:VTODO"));
+        assert!(!taskwarrior::export(&other, &["Inbox"]).contains("RRULE"));
+
+        let broken = "BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:x\nEND:VTODO\nEND:VCALENDAR\n";
+        let report = ics::import(&mut other, broken, "Inbox", &Foreign::new());
+        assert_eq!(report.errors, vec![(2, "VTODO has no SUMMARY".to_string())]);
+    }
+
+    #[test]
+    fn test_imports_keep_others_uids() -> Result<()> {
+        let mut conn = setup_db()?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        alice.get_or_add_todo_list("Work").add("Plan".to_string());
+        alice.push_to_db(&conn)?;
+        let alice_uid = alice.todo_lists["Work"].todos[0].uid.clone();
+
+        // Another user's export becomes new items with their own uids.
+        let mut bob = User::new("bob".to_string(), "bob@example.com".to_string());
+        let foreign = exchange::foreign_uids(&conn, "bob")?;
+        assert!(foreign.contains(&alice_uid));
+        let exported = ics::export(&alice, &["Work"]);
+        assert_eq!(
+            ics::import(&mut bob, &exported, "Inbox", &foreign).imported,
+            1
+        );
+        assert_ne!(bob.todo_lists["Work"].todos[0].uid, alice_uid);
+
+        // Re-importing an item that is in the trash doesn't reuse its uid.
+        bob.trash("Work", Some(1));
+        let trashed = bob.todo_lists["Work"].todos[0].uid.clone();
+        let text = format!(
+            r#"[{{"description":"Plan","status":"pending","uuid":"{}"}}]"#,
+            trashed
+        );
+        let report = taskwarrior::import(&mut bob, &text, "Inbox", &Foreign::new());
+        assert_eq!((report.imported, report.updated), (1, 0));
+        let list = &bob.todo_lists["Inbox"];
+        assert_ne!(list.todos[0].uid, trashed);
+
+        // Completions without COMPLETED get a completion time.
+        let calendar = "\
+BEGIN:VCALENDAR
+BEGIN:VTODO
+UID:done-1
+SUMMARY:Pay rent
+STATUS:COMPLETED
+LAST-MODIFIED:20240301T100000Z
+DUE:20240301T090000Z
+END:VTODO
+BEGIN:VTODO
+UID:done-2
+SUMMARY:Water plants
+STATUS:COMPLETED
+END:VTODO
+END:VCALENDAR
+";
+        ics::import(&mut bob, calendar, "Chores", &Foreign::new());
+        let chores = &bob.todo_lists["Chores"].todos;
+        assert_eq!(chores[0].completed_at, ics::from_ical("20240301T100000Z"));
+        assert_eq!(chores[0].due, ics::from_ical("20240301T090000Z"));
+        assert!(chores[1].completed_at.is_some());
+
+        // A merged dump can't overwrite another user's item either.
+        let mut dumped = dump::dump(&conn)?;
+        dumped.users[0].user_name = "bob".to_string();
+        dumped.users[0].email = "bob@example.com".to_string();
+        dumped.users[0].lists[0].items[0].title = "Taken over".to_string();
+        dump::restore(&mut conn, &dumped, true)?;
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert_eq!(alice.todo_lists["Work"].todos[0].title, "Plan");
+        let bob = User::pull_from_db(&conn, "bob")?;
+        assert_ne!(bob.todo_lists["Work"].todos[0].uid, alice_uid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_taskwarrior_round_trip() {
+        let text = r#"[
+{"id":1,"description":"Renew TLS cert","entry":"20240301T090000Z","modified":"20240302T090000Z","project":"Ops","status":"pending","tags":["infra","urgent"],"priority":"H","due":"20240315T000000Z","depends":"1a2b3c4d-0000-0000-0000-000000000000","uuid":"5f1c5d4e-9f0e-4a3b-8c2d-1e2f3a4b5c6d","annotations":[{"entry":"20240301T100000Z","description":"Ask the CA"}],"estimate":3,"urgency":12.3},
+{"id":0,"description":"Old chore","end":"20240305T120000Z","entry":"20240301T090000Z","status":"completed","uuid":"0e5c7a1b-2c3d-4e5f-8a9b-0c1d2e3f4a5b"},
+{"id":0,"description":"Gone","entry":"20240301T090000Z","status":"deleted","uuid":"aaaaaaaa-2c3d-4e5f-8a9b-0c1d2e3f4a5b"},
+{"id":2,"description":"  ","entry":"20240301T090000Z","status":"pending","uuid":"bbbbbbbb-2c3d-4e5f-8a9b-0c1d2e3f4a5b"},
+{"id":3,"description":"Sort receipts","entry":"20240301T090000Z","status":"pending","tags":["tax return"],"priority":"urgent","uuid":"cccccccc-2c3d-4e5f-8a9b-0c1d2e3f4a5b"}
+]
+"#;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let report = taskwarrior::import(&mut user, text, "Inbox", &Foreign::new());
+        assert_eq!(report.imported, 3);
+        assert_eq!(
+            report.errors,
+            vec![
+                (4, "task is deleted".to_string()),
+                (5, "missing description".to_string())
+            ]
+        );
+        let cert = &user.todo_lists["Ops"].todos[0];
+        assert_eq!(cert.priority, Some('A'));
+        assert_eq!(cert.tags, vec!["infra".to_string(), "urgent".to_string()]);
+        assert_eq!(cert.notes, "Ask the CA");
+        assert_eq!(
+            cert.due.as_deref(),
+            ics::from_ical("20240315T000000Z").as_deref()
+        );
+        assert!(!cert.extra.contains_key("urgency"));
+        let chore = &user.todo_lists["Inbox"].todos[0];
+        assert!(chore.is_completed);
+        assert!(chore.completed_at.is_some());
+        let receipts = &user.todo_lists["Inbox"].todos[1];
+        assert_eq!(receipts.tags, vec!["tax_return".to_string()]);
+        assert_eq!(receipts.priority, None);
+
+        let exported = taskwarrior::export(&user, &["Inbox", "Ops"]);
+        let tasks: Vec<serde_json::Value> = serde_json::from_str(&exported).unwrap();
+        assert_eq!(tasks[0]["status"], "completed");
+        assert_eq!(tasks[0]["end"], "20240305T120000Z");
+        assert_eq!(tasks[2]["uuid"], "5f1c5d4e-9f0e-4a3b-8c2d-1e2f3a4b5c6d");
+        assert_eq!(tasks[2]["entry"], "20240301T090000Z");
+        assert_eq!(tasks[2]["priority"], "H");
+        assert_eq!(tasks[2]["depends"], "1a2b3c4d-0000-0000-0000-000000000000");
+        assert_eq!(tasks[2]["estimate"], 3);
+        assert_eq!(tasks[2]["due"], "20240315T000000Z");
+        assert_eq!(tasks[2]["annotations"][0]["description"], "Ask the CA");
+        assert_eq!(tasks[2]["annotations"][0]["entry"], "20240301T100000Z");
+        assert_eq!(tasks[1]["tags"][0], "tax return");
+        assert_eq!(tasks[1]["priority"], "urgent");
+
+        let report = taskwarrior::import(&mut user, &exported, "Inbox", &Foreign::new());
+        assert_eq!((report.imported, report.updated), (0, 3));
+        assert_eq!(taskwarrior::export(&user, &["Inbox", "Ops"]), exported);
+
+        // Priorities past C survive a round trip through Taskwarrior.
+        let receipts = &mut user.todo_lists.get_mut("Inbox").unwrap().todos[1];
+        receipts.priority = Some('E');
+        receipts.tags = vec!["taxes".to_string()];
+        let exported = taskwarrior::export(&user, &["Inbox"]);
+        let tasks: Vec<serde_json::Value> = serde_json::from_str(&exported).unwrap();
+        assert_eq!(tasks[1]["priority"], "L");
+        assert_eq!(tasks[1]["tags"][0], "taxes");
+        taskwarrior::import(&mut user, &exported, "Inbox", &Foreign::new());
+        assert_eq!(user.todo_lists["Inbox"].todos[1].priority, Some('E'));
+    }
+
+    #[test]
+    fn test_extra_attributes_survive_push_and_pull() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let mut todo = crate::todo::TodoItem::new(0, "Wait for review".to_string());
+        todo.extra
+            .insert("wait".to_string(), serde_json::json!("20240401T000000Z"));
+        user.get_or_add_todo_list("Work").add_item(todo);
+        user.push_to_db(&conn)?;
+
+        let pulled = User::pull_from_db(&conn, "test_user")?;
+        assert_eq!(
+            pulled.todo_lists["Work"].todos[0].extra["wait"],
+            "20240401T000000Z"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_and_restore() -> Result<()> {
+        let mut conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let list = user.get_or_add_todo_list("Work");
+        list.add("Write report".to_string());
+        list.add("Review PR".to_string());
+        list.mark_complete(2);
+        list.todos[0].tags = vec!["docs".to_string()];
+        user.push_to_db(&conn)?;
+
+        let text = serde_json::to_string(&dump::dump(&conn)?).unwrap();
+        let backup = dump::parse(&text).unwrap();
+        assert_eq!(backup.version, SCHEMA_VERSION);
+
+        // A plain restore replaces everything, including items added since.
+        let mut fresh = setup_db()?;
+        User::new("other".to_string(), "o@example.com".to_string()).push_to_db(&fresh)?;
+        let report = dump::restore(&mut fresh, &backup, false).unwrap();
+        assert_eq!(report.inserted, 2);
+        assert!(User::pull_from_db(&fresh, "other").is_err());
+        let restored = User::pull_from_db(&fresh, "test_user")?;
+        let todos = &restored.todo_lists["Work"].todos;
+        assert_eq!(todos[0].uid, user.todo_lists["Work"].todos[0].uid);
+        assert_eq!(todos[0].tags, vec!["docs".to_string()]);
+        assert!(todos[1].is_completed);
+
+        // Merging updates items by uid and keeps ones the dump doesn't know.
+        let mut user = User::pull_from_db(&conn, "test_user")?;
+        let list = user.get_or_add_todo_list("Work");
+        list.todos[0].title = "Write final report".to_string();
+        list.todos[0].is_modified = true;
+        list.add("Plan sprint".to_string());
+        user.push_to_db(&conn)?;
+        let newer = dump::parse(&serde_json::to_string(&dump::dump(&conn)?).unwrap()).unwrap();
+        let mut local = User::pull_from_db(&fresh, "test_user")?;
+        local
+            .get_or_add_todo_list("Home")
+            .add("Water plants".to_string());
+        local.push_to_db(&fresh)?;
+        let report = dump::restore(&mut fresh, &newer, true).unwrap();
+        assert_eq!((report.inserted, report.updated), (1, 2));
+        let merged = User::pull_from_db(&fresh, "test_user")?;
+        assert_eq!(
+            merged.todo_lists["Work"].todos[0].title,
+            "Write final report"
+        );
+        assert_eq!(merged.todo_lists["Work"].todos.len(), 3);
+        assert_eq!(merged.todo_lists["Home"].todos.len(), 1);
+
+        // Logged time doesn't survive a plain restore, but the audit trail
+        // does, and records the restore against the replaced state.
+        timesheet::log(
+            &conn,
+            "test_user",
+            "Work",
+            &user.todo_lists["Work"].todos[0],
+            60,
+            false,
+        )?;
+        let events_before: i64 =
+            conn.query_row("SELECT COUNT(*) FROM todo_events", [], |row| row.get(0))?;
+        dump::restore(&mut conn, &backup, false).unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0));
+        assert_eq!(count("SELECT COUNT(*) FROM time_entries")?, 0);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM todo_events WHERE actor <> 'restore'")?,
+            events_before
+        );
+        let restore_events = conn
+            .prepare(
+                "SELECT kind, field, old_value FROM todo_events WHERE actor = 'restore'
+                ORDER BY id",
+            )?
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            restore_events,
+            vec![
+                (
+                    "edit".to_string(),
+                    Some("title".to_string()),
+                    Some("Write final report".to_string())
+                ),
+                ("delete".to_string(), None, Some("Plan sprint".to_string())),
+            ]
+        );
+        assert_eq!(
+            User::pull_from_db(&conn, "test_user")?.todo_lists["Work"]
+                .todos
+                .len(),
+            2
+        );
+
+        let future = format!(
+            r#"{{"version": {}, "users": [], "new_field": 1}}"#,
+            SCHEMA_VERSION + 1
+        );
+        assert!(dump::parse(&future)
+            .err()
+            .unwrap()
+            .contains("only supports up to"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_retention() {
+        let at = |day: u32, hour: u32| {
+            chrono::NaiveDate::from_ymd_opt(2024, 3, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        // Newest first: two on Friday the 15th, then the 14th, 13th, 8th and 1st.
+        let times = [
+            at(15, 18),
+            at(15, 9),
+            at(14, 9),
+            at(13, 9),
+            at(8, 9),
+            at(1, 9),
+        ];
+        assert_eq!(
+            backup::retained(&times, 2, 2),
+            vec![true, false, true, false, true, false]
+        );
+        let mut newest_only = vec![false; 6];
+        newest_only[0] = true;
+        assert_eq!(backup::retained(&times, 0, 0), newest_only);
+    }
+
+    #[test]
+    fn test_backup_and_restore() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("todo-backup-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.get_or_add_todo_list("Work")
+            .add("Write report".to_string());
+        user.push_to_db(&conn)?;
+
+        let snapshot = backup::create(&conn, &dir).unwrap();
+        assert_eq!(backup::list(&dir).unwrap()[0].1, snapshot);
+        conn.execute("DELETE FROM todos", [])?;
+
+        let corrupt = dir.join("corrupt.db");
+        std::fs::write(&corrupt, b"not a database").unwrap();
+        assert!(backup::restore(&mut conn, &corrupt).is_err());
+
+        backup::restore(&mut conn, &snapshot).unwrap();
+        let restored = User::pull_from_db(&conn, "test_user")?;
+        assert_eq!(restored.todo_lists["Work"].todos[0].title, "Write report");
+        std::fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_redo_journal() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let mut history = Journal::load(&conn, "test_user")?;
+
+        let before = journal::snapshot(&user, None);
+        user.get_or_add_todo_list("Work")
+            .add("Write report".to_string());
+        assert!(history.record(
+            &conn,
+            "add Work \"Write report\"",
+            &before,
+            &journal::snapshot(&user, None)
+        )?);
+        let before = journal::snapshot(&user, None);
+        user.get_or_add_todo_list("Work").mark_complete(1);
+        history.record(
+            &conn,
+            "complete Work 1",
+            &before,
+            &journal::snapshot(&user, None),
+        )?;
+        let before = journal::snapshot(&user, None);
+        user.todo_lists.remove("Work");
+        history.record(
+            &conn,
+            "remove Work",
+            &before,
+            &journal::snapshot(&user, None),
+        )?;
+        let before = journal::snapshot(&user, None);
+        assert!(!history.record(
+            &conn,
+            "complete Nowhere 1",
+            &before,
+            &journal::snapshot(&user, None)
+        )?);
+
+        assert_eq!(
+            history.undo(&conn, &mut user, 2)?.commands,
+            vec!["remove Work", "complete Work 1"]
+        );
+        let todo = &user.todo_lists["Work"].todos[0];
+        assert!(!todo.is_completed && !todo.is_deleted);
+
+        // History survives a restart.
+        let mut history = Journal::load(&conn, "test_user")?;
+        assert_eq!(history.redo(&conn, &mut user, 5)?.commands.len(), 2);
+        assert!(user.todo_lists["Work"].todos[0].is_deleted);
+        history.undo(&conn, &mut user, 3)?;
+        assert!(user.todo_lists["Work"].todos[0].is_deleted);
+
+        // A new operation drops the undone ones.
+        let before = journal::snapshot(&user, None);
+        user.get_or_add_todo_list("Home")
+            .add("Water plants".to_string());
+        history.record(
+            &conn,
+            "add Home \"Water plants\"",
+            &before,
+            &journal::snapshot(&user, None),
+        )?;
+        assert_eq!(history.operations.len(), 1);
+        assert_eq!(history.redo(&conn, &mut user, 1)?, Default::default());
+
+        // Nothing is undone over a state the operation didn't leave, such as
+        // the empty lists of a fresh login.
+        let mut fresh = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let undone = history.undo(&conn, &mut fresh, 1)?;
+        assert!(undone.commands.is_empty());
+        assert_eq!(
+            undone.refused.unwrap().1,
+            "item 1 of Home has changed since, or hasn't been pulled"
+        );
+        assert!(fresh.todo_lists.is_empty());
+
+        // Pushed comments can't be taken back.
+        let before = journal::snapshot(&user, Some("Home"));
+        let author = user.user_name.clone();
+        user.get_or_add_todo_list("Home")
+            .comment(1, &author, "Done twice a week".to_string());
+        history.record(
+            &conn,
+            "comment Home 1",
+            &before,
+            &journal::snapshot(&user, Some("Home")),
+        )?;
+        user.push_to_db(&conn)?;
+        let undone = history.undo(&conn, &mut user, 1)?;
+        assert_eq!(
+            undone.refused.unwrap().1,
+            "a comment on item 1 of Home has been pushed"
+        );
+        assert_eq!(user.todo_lists["Home"].todos[0].comments.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_item_events() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        // Like the REPL: events are queued as changes are made.
+        let change = |user: &mut User, change: &dyn Fn(&mut User)| -> Result<()> {
+            let before = journal::snapshot(user, None);
+            change(user);
+            events::queue(&conn, user, &before, &journal::snapshot(user, None))
+        };
+        let step = |user: &mut User, edit: &dyn Fn(&mut User)| -> Result<()> {
+            change(user, edit)?;
+            user.push_to_db(&conn)
+        };
+        step(&mut user, &|user| {
+            user.get_or_add_todo_list("Work")
+                .add("Write report".to_string())
+        })?;
+        step(&mut user, &|user| {
+            let list = user.get_or_add_todo_list("Work");
+            list.mark_complete(1);
+            list.append_note(1, "sent to Sam");
+        })?;
+        step(&mut user, &|user| {
+            user.get_or_add_todo_list("Work").mark_incomplete(1)
+        })?;
+        // Unpushed changes show as such until a pull drops them.
+        change(&mut user, &|user| {
+            user.get_or_add_todo_list("Work")
+                .set_notes(1, "draft".to_string());
+        })?;
+        let uid = user.todo_lists["Work"].todos[0].uid.clone();
+        let pending = events::timeline(&conn, &uid, "test_user")?;
+        assert_eq!(pending.len(), 5);
+        assert!(!pending[4].pushed && pending[3].pushed);
+        assert_eq!(events::timeline(&conn, &uid, "someone_else")?.len(), 4);
+        events::discard(&conn, "test_user", None)?;
+        let mut user = User::pull_from_db(&conn, "test_user")?;
+        step(&mut user, &|user| {
+            user.get_or_add_todo_list("Work").remove_item(1)
+        })?;
+
+        let uid = events::find_uid(&conn, "test_user", "Work", 1)?.unwrap();
+        let timeline: Vec<String> = events::timeline(&conn, &uid, "test_user")?
+            .iter()
+            .map(|event| format!("{} {}", event.actor, event.describe()))
+            .collect();
+        assert_eq!(
+            timeline,
+            vec![
+                "test_user created \"Write report\"",
+                "test_user edited notes: \"\" -> \"sent to Sam\"",
+                "test_user completed",
+                "test_user marked incomplete",
+                "test_user deleted \"Write report\"",
+            ]
+        );
+        assert!(conn.execute("DELETE FROM todo_events", []).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trash_restore_and_purge() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let work = user.get_or_add_todo_list("Work");
+        work.add("Write report".to_string());
+        work.add("Send invoice".to_string());
+        user.get_or_add_todo_list("Home")
+            .add("Water plants".to_string());
+        assert_eq!(user.trash("Work", None), 2);
+        assert_eq!(user.trash("Home", Some(1)), 1);
+        user.push_to_db(&conn)?;
+
+        let mut user = User::pull_from_db(&conn, "test_user")?;
+        let entries = trash::entries(&user);
+        let summary: Vec<(usize, &str, usize)> = entries
+            .iter()
+            .map(|entry| (entry.trash.id, entry.list_name, entry.items.len()))
+            .collect();
+        assert_eq!(summary, vec![(2, "Home", 1), (1, "Work", 2)]);
+
+        assert_eq!(user.restore_from_trash(1), 2);
+        assert!(user.todo_lists["Work"]
+            .todos
+            .iter()
+            .all(|todo| !todo.is_deleted));
+        user.push_to_db(&conn)?;
+
+        // Nothing is old enough to purge yet.
+        assert_eq!(trash::purge_expired(&conn, 1)?, 0);
+        conn.execute(
+            "UPDATE todos SET deleted_at = '2000-01-01 00:00:00' WHERE trash_id IS NOT NULL",
+            [],
+        )?;
+        assert_eq!(trash::purge_expired(&conn, 30)?, 1);
+        let remaining: usize =
+            conn.query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0))?;
+        assert_eq!(remaining, 2);
+
+        user.get_or_add_todo_list("Work")
+            .add("Call Sam".to_string());
+        user.trash("Work", Some(3));
+        assert_eq!(user.empty_trash(), 2);
+        user.push_to_db(&conn)?;
+        let remaining: usize =
+            conn.query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0))?;
+        assert_eq!(remaining, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_and_unarchive() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let work = user.get_or_add_todo_list("Work");
+        work.add("Write report".to_string());
+        work.add("Renew tls certificate".to_string());
+        work.add("Send invoice".to_string());
+        work.mark_complete(1);
+        work.mark_complete(2);
+        assert_eq!(work.archive(true), Err(1));
+        assert_eq!(work.archive(false), Ok(2));
+        assert_eq!(work.unarchive(Some(1)), 1);
+        user.push_to_db(&conn)?;
+
+        let mut other = User::new("other".to_string(), "other@example.com".to_string());
+        let done = other.get_or_add_todo_list("Done");
+        done.add("Ship it".to_string());
+        done.mark_complete(1);
+        assert_eq!(done.archive(true), Ok(1));
+
+        let live: Vec<usize> = listing::rows(&user, None, false)
+            .iter()
+            .map(|row| row.todo.item_number)
+            .collect();
+        assert_eq!(live, vec![1, 3]);
+        assert_eq!(listing::rows(&user, None, true).len(), 1);
+        assert!(search(&user, "certificate", false)?.is_empty());
+        assert_eq!(search(&user, "certificate", true)?.len(), 1);
+
+        // Only items completed long enough ago are archived automatically.
+        conn.execute(
+            "UPDATE todos SET completed_at = '2000-01-01 00:00:00' WHERE item_number = 1",
+            [],
+        )?;
+        assert_eq!(archive::archive_completed(&conn, 30)?, 1);
+        assert_eq!(archive::archive_completed(&conn, 30)?, 0);
+        let user = User::pull_from_db(&conn, "test_user")?;
+        assert_eq!(listing::rows(&user, None, true).len(), 2);
+        assert_eq!(
+            events::find_uid(&conn, "test_user", "Work", 1)?,
+            Some(user.todo_lists["Work"].todos[0].uid.clone())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let work = user.get_or_add_todo_list("Work");
+        for (title, created, completed_at) in [
+            (
+                "Write report",
+                "2024-03-01 09:00:00",
+                Some("2024-03-04 21:00:00"),
+            ),
+            (
+                "Send invoice",
+                "2024-03-04 10:00:00",
+                Some("2024-03-05 10:00:00"),
+            ),
+            ("Renew certificate", "2024-02-20 08:00:00", None),
+        ] {
+            work.add(title.to_string());
+            let todo = work.todos.last_mut().unwrap();
+            todo.datetime = created.to_string();
+            todo.is_completed = completed_at.is_some();
+            todo.completed_at = completed_at.map(String::from);
+        }
+        let home = user.get_or_add_todo_list("Home");
+        home.add("Water plants".to_string());
+        home.todos[0].datetime = "2024-03-05 12:00:00".to_string();
+
+        let now =
+            NaiveDateTime::parse_from_str("2024-03-06 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let report = stats::stats(&user, Period::Week, 2, 1, now);
+        assert_eq!(
+            report.periods,
+            vec![
+                stats::PeriodCount {
+                    period: "2024-W09".to_string(),
+                    created: 1,
+                    completed: 0,
+                },
+                stats::PeriodCount {
+                    period: "2024-W10".to_string(),
+                    created: 2,
+                    completed: 2,
+                },
+            ]
+        );
+        let rates: Vec<(&str, usize, usize)> = report
+            .lists
+            .iter()
+            .map(|list| (list.list.as_str(), list.completed, list.total))
+            .collect();
+        assert_eq!(rates, vec![("Home", 0, 1), ("Work", 2, 3)]);
+        assert_eq!(report.average_days_to_complete, Some(2.25));
+        assert_eq!(report.oldest_open.len(), 1);
+        assert_eq!(report.oldest_open[0].title, "Renew certificate");
+        assert_eq!(report.oldest_open[0].age_days, 15);
+
+        let months = stats::stats(&user, Period::Month, 3, 5, now);
+        let labels: Vec<&str> = months.periods.iter().map(|p| p.period.as_str()).collect();
+        assert_eq!(labels, vec!["2024-01", "2024-02", "2024-03"]);
+    }
+
+    #[test]
+    fn test_charts() {
+        let day = |text: &str| NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap();
+        let today = day("2024-03-06");
+        let heatmap = chart::heatmap(
+            &[day("2024-03-04"), day("2024-03-04"), day("2024-03-05")],
+            today,
+        );
+        assert_eq!(heatmap.len(), 9);
+        assert!(heatmap[0].trim_start().starts_with("Mar"));
+        // Wednesday is the last day drawn; Monday was busiest.
+        assert!(heatmap[1].ends_with('█'));
+        assert!(heatmap[2].ends_with('▒'));
+        assert!(heatmap[3].ends_with('·'));
+        assert_eq!(heatmap[4].chars().count(), heatmap[3].chars().count() - 1);
+        assert!(heatmap[8].contains("3 completions"));
+
+        let history = [
+            (day("2024-03-04"), Some(day("2024-03-05"))),
+            (day("2024-03-04"), None),
+            (day("2024-03-05"), Some(day("2024-03-06"))),
+            (day("2024-03-06"), None),
+        ];
+        let chart = chart::burndown(&history, day("2024-03-04"), today, 4);
+        assert_eq!(
+            chart[..5],
+            [
+                "4 │  ░".to_string(),
+                "  │ ░░".to_string(),
+                "  │░░█".to_string(),
+                "0 │░██".to_string(),
+                "  └───".to_string(),
+            ]
+        );
+        assert!(chart.last().unwrap().contains("done (2)  ░ open (2)"));
+    }
+
+    #[test]
+    fn test_timers_and_timesheet() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        let work = user.get_or_add_todo_list("Work");
+        work.add("Write report".to_string());
+        work.add("Send invoice".to_string());
+        let work = &user.todo_lists["Work"];
+
+        assert_eq!(timesheet::parse_duration("1h30m"), Ok(5400));
+        assert!(timesheet::parse_duration("90").is_err());
+        assert!(timesheet::parse_duration("99999999999h").is_err());
+        assert!(timesheet::parse_duration("99999999999999999999s").is_err());
+        assert_eq!(
+            timesheet::parse_duration("168h"),
+            Ok(timesheet::MAX_DURATION)
+        );
+        assert!(timesheet::parse_duration("168h1s").is_err());
+        assert_eq!(timesheet::format_duration(5400), "1h30m");
+        assert_eq!(timesheet::format_duration(2700), "45m");
+
+        assert!(timesheet::start(&conn, "test_user", "Work", &work.todos[0])?.is_none());
+        // Pretend the timer has been running for two hours.
+        conn.execute(
+            "UPDATE time_entries SET started_at = datetime(started_at, '-2 hours')",
+            [],
+        )?;
+        let (stopped, seconds) =
+            timesheet::start(&conn, "test_user", "Work", &work.todos[1])?.unwrap();
+        assert_eq!(stopped.item_number, 1);
+        assert!((7200..7205).contains(&seconds));
+        assert_eq!(
+            timesheet::active(&conn, "test_user")?.unwrap().item_number,
+            2
+        );
+        timesheet::stop(&conn, "test_user")?;
+        assert!(timesheet::active(&conn, "test_user")?.is_none());
+        // Pin the timed entries to exact lengths so the totals below are exact.
+        conn.execute(
+        "UPDATE time_entries SET ended_at = datetime(started_at, '+' || (3 - item_number) || ' hours')",
+        [],
+    )?;
+        timesheet::log(&conn, "test_user", "Work", &work.todos[0], 1800, false)?;
+
+        let lines = timesheet::summarise(&conn, &user, None)?;
+        let summary: Vec<(usize, &str, i64)> = lines
+            .iter()
+            .map(|line| (line.item, line.title.as_str(), line.seconds))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(1, "Write report", 9000), (2, "Send invoice", 3600)]
+        );
+        assert_eq!(
+            timesheet::to_csv(&lines),
+            "list,item,title,hours\nWork,1,Write report,2.50\nWork,2,Send invoice,1.00\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_focus_logs_pomodoros() -> Result<()> {
+        let conn = setup_db()?;
+        let mut user = User::new(
+            "test_user".to_string(),
+            "test_email@example.com".to_string(),
+        );
+        user.get_or_add_todo_list("Work")
+            .add("Write report".to_string());
+        let todo = &user.todo_lists["Work"].todos[0];
+
+        assert_eq!(focus::run(&conn, "test_user", "Work", todo, 0, 0, 3)?, 3);
+        timesheet::log(&conn, "test_user", "Work", todo, 600, false)?;
+        assert_eq!(
+            timesheet::tracked(&conn, "test_user", &todo.uid)?,
+            timesheet::Tracked {
+                seconds: 600,
+                pomodoros: 3,
+            }
+        );
+        assert_eq!(
+            timesheet::tracked(&conn, "other_user", &todo.uid)?,
+            timesheet::Tracked::default()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_list_roles() -> Result<()> {
+        let conn = setup_db()?;
+        let mut bob = User::new("bob".to_string(), "bob@example.com".to_string());
+        bob.push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        alice
+            .get_or_add_todo_list("Work")
+            .add("Write report".to_string());
+        alice.push_to_db(&conn)?;
+        assert_eq!(
+            sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Editor)?,
+            Some("bob".to_string())
+        );
+        assert_eq!(
+            sharing::share(&conn, "alice", "Work", "nobody@example.com", Role::Editor)?,
+            None
+        );
+
+        let mut bob = User::pull_from_db(&conn, "bob")?;
+        let shared = &bob.todo_lists["alice/Work"];
+        assert_eq!(shared.shared.as_ref().unwrap().owner, "alice");
+        assert!(bob.can_edit("alice/Work") && bob.manageable("alice/Work").is_none());
+        bob.get_or_add_todo_list("alice/Work")
+            .add("Send invoice".to_string());
+        bob.push_to_db(&conn)?;
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert_eq!(alice.todo_lists["Work"].todos.len(), 2);
+
+        // Demoting bob takes effect at push, even with lists pulled before it.
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Viewer)?;
+        bob.get_or_add_todo_list("alice/Work").mark_complete(1);
+        let error = bob.push_to_db(&conn).unwrap_err();
+        assert!(error.to_string().contains("you can only view alice/Work"));
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert!(!alice.todo_lists["Work"].todos[0].is_completed);
+        let mut bob = User::pull_from_db(&conn, "bob")?;
+        assert!(!bob.can_edit("alice/Work"));
+
+        // Viewers can still comment, but nothing else goes along with it.
+        let shared = bob.get_or_add_todo_list("alice/Work");
+        assert!(shared.comment(1, "bob", "Need a hand?".to_string()));
+        bob.push_to_db(&conn)?;
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert_eq!(alice.todo_lists["Work"].todos[0].comments.len(), 1);
+        let shared = bob.get_or_add_todo_list("alice/Work");
+        shared.comment(2, "bob", "And this one?".to_string());
+        shared.set_notes(2, "bob was here".to_string());
+        assert!(bob.push_to_db(&conn).is_err());
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert!(alice.todo_lists["Work"].todos[1].comments.is_empty());
+
+        assert!(sharing::unshare(&conn, "alice", "Work", "bob@example.com")?);
+        let bob = User::pull_from_db(&conn, "bob")?;
+        assert!(bob.todo_lists.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_notifications() -> Result<()> {
+        let conn = setup_db()?;
+        User::new("bob".to_string(), "bob@example.com".to_string()).push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        alice.get_or_add_todo_list("Work").add("Plan".to_string());
+        alice.push_to_db(&conn)?;
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Editor)?;
+        sharing::unshare(&conn, "alice", "Work", "bob@example.com")?;
+        // Nothing is sent for lists that weren't shared with the user.
+        sharing::unshare(&conn, "alice", "Work", "bob@example.com")?;
+        sharing::share(&conn, "alice", "Work", "alice@example.com", Role::Editor)?;
+        assert_eq!(notifications::unread_count(&conn, "alice")?, 0);
+
+        let inbox = notifications::list(&conn, "bob", false)?;
+        let messages: Vec<&str> = inbox.iter().map(|n| n.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "alice stopped sharing Work with you.",
+                "alice shared Work with you as editor."
+            ]
+        );
+        assert_eq!(
+            notifications::mark_read(&conn, "bob", Some(inbox[1].id))?,
+            1
+        );
+        assert_eq!(
+            notifications::mark_read(&conn, "alice", Some(inbox[0].id))?,
+            0
+        );
+        assert_eq!(notifications::unread_count(&conn, "bob")?, 1);
+        assert_eq!(notifications::list(&conn, "bob", true)?[0].kind, "unshare");
+        let mut inbox = notifications::list(&conn, "bob", false)?;
+        notifications::sort(
+            &mut inbox,
+            &[notifications::parse_sort_key("created:asc").unwrap()],
+        );
+        assert_eq!(inbox[0].kind, "share");
+        let groups = notifications::group(inbox, notifications::GroupBy::Status);
+        let names: Vec<&str> = groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Unread", "Read"]);
+        assert!(notifications::parse_sort_key("title").is_err());
+        assert_eq!(notifications::mark_read(&conn, "bob", None)?, 1);
+        assert!(notifications::list(&conn, "bob", true)?.is_empty());
+        assert_eq!(notifications::list(&conn, "bob", false)?.len(), 2);
+
+        let record = serde_json::to_value(&notifications::list(&conn, "bob", false)?[0]).unwrap();
+        let keys: Vec<&str> = record
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(keys, ["created_at", "id", "kind", "message", "read_at"]);
+        assert!(record["read_at"].is_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_overdue_notifications() -> Result<()> {
+        let conn = setup_db()?;
+        User::new("bob".to_string(), "bob@example.com".to_string()).push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        let work = alice.get_or_add_todo_list("Work");
+        work.add("Plan".to_string());
+        work.add("Review".to_string());
+        work.add("Ship".to_string());
+        work.set_due(1, Some(filter::parse_due("yesterday").unwrap()));
+        work.set_due(2, Some(filter::parse_due("-3d").unwrap()));
+        work.set_due(3, Some(filter::parse_due("tomorrow").unwrap()));
+        work.assign(2, Some("bob".to_string()));
+        alice.push_to_db(&conn)?;
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Editor)?;
+
+        // The assignee is told instead of the owner, and only once.
+        assert_eq!(notifications::notify_overdue(&conn)?, 2);
+        assert_eq!(notifications::notify_overdue(&conn)?, 0);
+        let inbox = notifications::list(&conn, "alice", true)?;
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].kind, "due");
+        assert!(inbox[0].message.starts_with("\"Plan\" in Work was due"));
+        let inbox = notifications::list(&conn, "bob", true)?;
+        assert!(inbox
+            .iter()
+            .any(|n| n.kind == "due" && n.message.contains("alice/Work")));
+
+        // A new due date that passes is reported again.
+        let work = alice.get_or_add_todo_list("Work");
+        work.set_due(1, Some(filter::parse_due("-2d").unwrap()));
+        alice.push_to_db(&conn)?;
+        assert_eq!(notifications::notify_overdue(&conn)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_assign_items() -> Result<()> {
+        let conn = setup_db()?;
+        User::new("bob".to_string(), "bob@example.com".to_string()).push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        alice.get_or_add_todo_list("Work").add("Plan".to_string());
+        alice.push_to_db(&conn)?;
+        assert!(!sharing::has_access(&conn, "alice", "Work", "bob")?);
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Editor)?;
+        assert!(sharing::has_access(&conn, "alice", "Work", "bob")?);
+        assert!(sharing::has_access(&conn, "alice", "Work", "alice")?);
+
+        let list = alice.get_or_add_todo_list("Work");
+        assert!(list.assign(1, Some("bob".to_string())));
+        assert!(!list.assign(2, Some("bob".to_string())));
+        // Assignees hear about changes once they are pushed.
+        assert_eq!(notifications::unread_count(&conn, "bob")?, 1);
+        alice.push_to_db(&conn)?;
+
+        let mut bob = User::pull_from_db(&conn, "bob")?;
+        assert_eq!(
+            bob.todo_lists["alice/Work"].todos[0].assignee.as_deref(),
+            Some("bob")
+        );
+        let mine = filter::parse("assignee:bob").unwrap();
+        assert_eq!(listing::rows(&bob, Some(&mine), false).len(), 1);
+        let theirs = filter::Filter::assignee("alice");
+        assert!(listing::rows(&bob, Some(&theirs), false).is_empty());
+
+        // Bob's own changes don't notify him; alice's do.
+        bob.get_or_add_todo_list("alice/Work").mark_complete(1);
+        bob.push_to_db(&conn)?;
+        alice
+            .get_or_add_todo_list("Work")
+            .set_notes(1, "Q3".to_string());
+        alice.push_to_db(&conn)?;
+        alice.get_or_add_todo_list("Work").assign(1, None);
+        let messages: Vec<String> = notifications::list(&conn, "bob", false)?
+            .into_iter()
+            .map(|n| n.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "alice changed \"Plan\" in alice/Work.",
+                "alice assigned you \"Plan\" in alice/Work.",
+                "alice shared Work with you as editor."
+            ]
+        );
+
+        let dump = dump::dump(&conn)?;
+        assert_eq!(
+            dump.users[0].lists[0].items[0].assignee.as_deref(),
+            Some("bob")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments_and_mentions() -> Result<()> {
+        assert_eq!(
+            comments::mentions("@bob, see @carol's note; @bob again, not me@example.com"),
+            ["bob", "carol"]
+        );
+
+        let mut conn = setup_db()?;
+        User::new("bob".to_string(), "bob@example.com".to_string()).push_to_db(&conn)?;
+        User::new("carol".to_string(), "carol@example.com".to_string()).push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        alice.get_or_add_todo_list("Work").add("Plan".to_string());
+        alice.push_to_db(&conn)?;
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Editor)?;
+
+        let list = alice.get_or_add_todo_list("Work");
+        assert!(list.comment(1, "alice", "@bob @carol @alice thoughts?".to_string()));
+        assert!(!list.comment(2, "alice", "nothing here".to_string()));
+        alice.push_to_db(&conn)?;
+        alice.push_to_db(&conn)?;
+
+        // Carol can't see the list, and alice wrote the comment.
+        assert!(notifications::list(&conn, "carol", false)?.is_empty());
+        assert_eq!(notifications::unread_count(&conn, "alice")?, 0);
+        let mut bob = User::pull_from_db(&conn, "bob")?;
+        assert_eq!(bob.todo_lists["alice/Work"].todos[0].comments.len(), 1);
+        bob.get_or_add_todo_list("alice/Work")
+            .comment(1, "bob", "Looks good @alice".to_string());
+        bob.push_to_db(&conn)?;
+
+        let alice = User::pull_from_db(&conn, "alice")?;
+        let thread: Vec<(&str, &str)> = alice.todo_lists["Work"].todos[0]
+            .comments
+            .iter()
+            .map(|c| (c.author.as_str(), c.text.as_str()))
+            .collect();
+        assert_eq!(
+            thread,
+            [
+                ("alice", "@bob @carol @alice thoughts?"),
+                ("bob", "Looks good @alice")
+            ]
+        );
+        assert_eq!(
+            notifications::list(&conn, "bob", true)?[0].message,
+            "alice mentioned you on \"Plan\" in alice/Work: @bob @carol @alice thoughts?"
+        );
+        assert_eq!(
+            notifications::list(&conn, "alice", true)?[0].message,
+            "bob mentioned you on \"Plan\" in Work: Looks good @alice"
+        );
+
+        let dumped = dump::dump(&conn)?;
+        dump::restore(&mut conn, &dumped, false)?;
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert_eq!(alice.todo_lists["Work"].todos[0].comments.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pushes_keep_others_changes() -> Result<()> {
+        let conn = setup_db()?;
+        User::new("bob".to_string(), "bob@example.com".to_string()).push_to_db(&conn)?;
+        User::new("carol".to_string(), "carol@example.com".to_string()).push_to_db(&conn)?;
+        let mut alice = User::new("alice".to_string(), "alice@example.com".to_string());
+        let list = alice.get_or_add_todo_list("Work");
+        list.add("Write report".to_string());
+        list.add("Old idea".to_string());
+        alice.push_to_db(&conn)?;
+        alice.trash("Work", Some(2));
+        alice.push_to_db(&conn)?;
+        sharing::share(&conn, "alice", "Work", "bob@example.com", Role::Editor)?;
+        sharing::share(&conn, "alice", "Work", "carol@example.com", Role::Viewer)?;
+
+        let mut stale = User::pull_from_db(&conn, "alice")?;
+        let mut bob = User::pull_from_db(&conn, "bob")?;
+        let before = journal::snapshot(&bob, None);
+        let shared = bob.get_or_add_todo_list("alice/Work");
+        shared.mark_complete(1);
+        shared.add("Send invoice".to_string());
+        events::queue(&conn, &bob, &before, &journal::snapshot(&bob, None))?;
+        bob.push_to_db(&conn)?;
+        let before = journal::snapshot(&stale, None);
+        stale
+            .get_or_add_todo_list("Work")
+            .add("Book room".to_string());
+        events::queue(&conn, &stale, &before, &journal::snapshot(&stale, None))?;
+        stale.push_to_db(&conn)?;
+        assert_eq!(stale.todo_lists["Work"].todos[2].item_number, 4);
+
+        let alice = User::pull_from_db(&conn, "alice")?;
+        let titles: Vec<(usize, &str, bool)> = alice.todo_lists["Work"]
+            .todos
+            .iter()
+            .map(|todo| (todo.item_number, todo.title.as_str(), todo.is_completed))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                (1, "Write report", true),
+                (2, "Old idea", false),
+                (3, "Send invoice", false),
+                (4, "Book room", false)
+            ]
+        );
+
+        // Members' edits are filed under the owner's list.
+        let uid = events::find_uid(&conn, "alice", "Work", 3)?.unwrap();
+        assert_eq!(events::timeline(&conn, &uid, "alice")?[0].actor, "bob");
+        assert_eq!(events::find_uid(&conn, "bob", "alice/Work", 3)?, None);
+        // An item renumbered on push is logged under its new number.
+        let uid = events::find_uid(&conn, "alice", "Work", 4)?.unwrap();
+        assert_eq!(
+            events::timeline(&conn, &uid, "alice")?[0].describe(),
+            "created \"Book room\""
+        );
+
+        // A viewer seeing an item in the owner's trash can still push their own lists.
+        let mut carol = User::pull_from_db(&conn, "carol")?;
+        carol
+            .get_or_add_todo_list("Home")
+            .add("Water plants".to_string());
+        carol.push_to_db(&conn)?;
+
+        // An item from someone else's export can't take over or delete theirs.
+        let original = alice.todo_lists["Work"].todos[0].uid.clone();
+        for copier in ["carol", "bob"] {
+            let mut copier = User::pull_from_db(&conn, copier)?;
+            let list_name = "Imported";
+            let mut copy = TodoItem::new(0, "Hijacked".to_string());
+            copy.uid = original.clone();
+            copier.get_or_add_todo_list(list_name).add_item(copy);
+            copier.push_to_db(&conn)?;
+            let copied = &copier.todo_lists[list_name].todos.last().unwrap();
+            assert_ne!(copied.uid, original);
+            copier.trash(list_name, Some(copied.item_number));
+            copier.empty_trash();
+            copier.push_to_db(&conn)?;
+        }
+        let mut copier = User::pull_from_db(&conn, "bob")?;
+        let mut copy = TodoItem::new(0, "Gone".to_string());
+        copy.uid = original.clone();
+        copy.is_deleted = true;
+        copier.get_or_add_todo_list("Mine").todos.push(copy);
+        copier.push_to_db(&conn)?;
+        let alice = User::pull_from_db(&conn, "alice")?;
+        assert_eq!(alice.todo_lists["Work"].todos[0].uid, original);
+        assert_eq!(alice.todo_lists["Work"].todos[0].title, "Write report");
+        Ok(())
+    }
 }
@@ -0,0 +1,314 @@
+//! Time tracking. Time spent on an item is recorded as entries in the
+//! `time_entries` table, keyed by the item's uid so they follow it when it is
+//! renumbered or moved. A running timer is an entry without an end; each user
+//! has at most one, and since it lives in the database it survives restarts.
+
+use crate::output::csv_field;
+use crate::table;
+use crate::todo::{get_current_date_time, TodoItem, User};
+use chrono::{Duration, NaiveDateTime};
+use clap::ValueEnum;
+use rusqlite::{params, Connection, Error, OptionalExtension, Result};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// The longest duration [`parse_duration`] accepts: a week.
+pub const MAX_DURATION: i64 = 7 * 24 * 3600;
+
+pub struct Timer {
+    pub id: i64,
+    pub list_name: String,
+    pub item_number: usize,
+    pub started_at: String,
+}
+
+fn seconds_between(start: &str, end: &str) -> i64 {
+    match (
+        NaiveDateTime::parse_from_str(start, TIME_FORMAT),
+        NaiveDateTime::parse_from_str(end, TIME_FORMAT),
+    ) {
+        (Ok(start), Ok(end)) => (end - start).num_seconds().max(0),
+        _ => 0,
+    }
+}
+
+/// Parses durations such as `1h30m`, `45m`, `2h` or `90s`, up to [`MAX_DURATION`].
+pub fn parse_duration(text: &str) -> std::result::Result<i64, String> {
+    let invalid = || format!("invalid duration '{}', expected e.g. 1h30m", text);
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        seconds = number
+            .parse::<i64>()
+            .ok()
+            .and_then(|number| number.checked_mul(unit))
+            .and_then(|part| part.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+    if seconds > MAX_DURATION {
+        return Err(format!(
+            "duration '{}' is longer than {}",
+            text,
+            format_duration(MAX_DURATION)
+        ));
+    }
+    Ok(seconds)
+}
+
+/// Formats seconds as `1h30m`, or `45m` under an hour.
+pub fn format_duration(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h{:02}m", hours, minutes),
+    }
+}
+
+pub fn active(conn: &Connection, user_name: &str) -> Result<Option<Timer>> {
+    conn.query_row(
+        "SELECT id, list_name, item_number, started_at FROM time_entries
+        WHERE user_name = ?1 AND ended_at IS NULL",
+        params![user_name],
+        |row| {
+            Ok(Timer {
+                id: row.get(0)?,
+                list_name: row.get(1)?,
+                item_number: row.get(2)?,
+                started_at: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Stops the running timer, returning it and how many seconds it ran.
+pub fn stop(conn: &Connection, user_name: &str) -> Result<Option<(Timer, i64)>> {
+    let Some(timer) = active(conn, user_name)? else {
+        return Ok(None);
+    };
+    let now = get_current_date_time();
+    conn.execute(
+        "UPDATE time_entries SET ended_at = ?2 WHERE id = ?1",
+        params![timer.id, now],
+    )?;
+    let seconds = seconds_between(&timer.started_at, &now);
+    Ok(Some((timer, seconds)))
+}
+
+/// Starts a timer on an item, first stopping any that is running, which is
+/// returned as by [`stop`].
+pub fn start(
+    conn: &Connection,
+    user_name: &str,
+    list_name: &str,
+    todo: &TodoItem,
+) -> Result<Option<(Timer, i64)>> {
+    let stopped = stop(conn, user_name)?;
+    conn.execute(
+        "INSERT INTO time_entries (user_name, uid, list_name, item_number, started_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            user_name,
+            todo.uid,
+            list_name,
+            todo.item_number,
+            get_current_date_time()
+        ],
+    )?;
+    Ok(stopped)
+}
+
+/// Records `seconds` of time on an item, ending now; `pomodoro` marks a
+/// finished `todo focus` work period.
+pub fn log(
+    conn: &Connection,
+    user_name: &str,
+    list_name: &str,
+    todo: &TodoItem,
+    seconds: i64,
+    pomodoro: bool,
+) -> Result<()> {
+    let now = NaiveDateTime::parse_from_str(&get_current_date_time(), TIME_FORMAT).unwrap();
+    let Some(start) = Duration::try_seconds(seconds).and_then(|ago| now.checked_sub_signed(ago))
+    else {
+        return Err(Error::ToSqlConversionFailure(
+            format!("{} seconds is out of range", seconds).into(),
+        ));
+    };
+    conn.execute(
+        "INSERT INTO time_entries (user_name, uid, list_name, item_number, started_at, ended_at,
+            pomodoro)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            user_name,
+            todo.uid,
+            list_name,
+            todo.item_number,
+            start.format(TIME_FORMAT).to_string(),
+            now.format(TIME_FORMAT).to_string(),
+            pomodoro as i32
+        ],
+    )?;
+    Ok(())
+}
+
+/// All the time recorded on one item.
+#[derive(Debug, Default, PartialEq)]
+pub struct Tracked {
+    pub seconds: i64,
+    pub pomodoros: usize,
+}
+
+pub fn tracked(conn: &Connection, user_name: &str, uid: &str) -> Result<Tracked> {
+    let mut stmt = conn.prepare(
+        "SELECT started_at, ended_at, pomodoro FROM time_entries
+        WHERE user_name = ?1 AND uid = ?2",
+    )?;
+    let now = get_current_date_time();
+    let mut tracked = Tracked::default();
+    let rows = stmt.query_map(params![user_name, uid], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, i32>(2)? != 0,
+        ))
+    })?;
+    for row in rows {
+        let (started_at, ended_at, pomodoro) = row?;
+        tracked.seconds += seconds_between(&started_at, ended_at.as_deref().unwrap_or(&now));
+        tracked.pomodoros += pomodoro as usize;
+    }
+    Ok(tracked)
+}
+
+/// Time spent on one item.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub list: String,
+    pub item: usize,
+    pub title: String,
+    pub seconds: i64,
+}
+
+/// Totals the user's time per item for entries started at or after `since`,
+/// ordered by list and item. A running timer counts up to now. Items are
+/// named as they are now, or as they were when the time was recorded if
+/// they are gone.
+pub fn summarise(conn: &Connection, user: &User, since: Option<&str>) -> Result<Vec<Line>> {
+    let mut stmt = conn.prepare(
+        "SELECT uid, list_name, item_number, started_at, ended_at FROM time_entries
+        WHERE user_name = ?1 AND started_at >= ?2",
+    )?;
+    let now = get_current_date_time();
+    let rows = stmt.query_map(params![user.user_name, since.unwrap_or("")], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, usize>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+    let mut lines: Vec<Line> = Vec::new();
+    let mut uids: Vec<String> = Vec::new();
+    for row in rows {
+        let (uid, list_name, item_number, started_at, ended_at) = row?;
+        let seconds = seconds_between(&started_at, ended_at.as_deref().unwrap_or(&now));
+        if let Some(index) = uids.iter().position(|known| *known == uid) {
+            lines[index].seconds += seconds;
+            continue;
+        }
+        let current = user.todo_lists.iter().find_map(|(name, list)| {
+            let todo = list.todos.iter().find(|todo| todo.uid == uid)?;
+            Some((name.clone(), todo.item_number, todo.title.clone()))
+        });
+        let (list, item, title) = current.unwrap_or((list_name, item_number, String::new()));
+        lines.push(Line {
+            list,
+            item,
+            title,
+            seconds,
+        });
+        uids.push(uid);
+    }
+    lines.sort_by(|a, b| (&a.list, a.item).cmp(&(&b.list, b.item)));
+    Ok(lines)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TimesheetFormat {
+    Text,
+    Csv,
+}
+
+/// A table of time per item with a subtotal per list and a grand total.
+pub fn to_text(lines: &[Line]) -> String {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        rows.push(vec![
+            line.list.clone(),
+            line.item.to_string(),
+            line.title.clone(),
+            format_duration(line.seconds),
+        ]);
+        if lines
+            .get(index + 1)
+            .is_none_or(|next| next.list != line.list)
+        {
+            let subtotal = lines
+                .iter()
+                .filter(|other| other.list == line.list)
+                .map(|other| other.seconds)
+                .sum();
+            rows.push(vec![
+                String::new(),
+                String::new(),
+                format!("Total for {}", line.list),
+                format_duration(subtotal),
+            ]);
+        }
+    }
+    let total = lines.iter().map(|line| line.seconds).sum();
+    rows.push(vec![
+        "Total".to_string(),
+        String::new(),
+        String::new(),
+        format_duration(total),
+    ]);
+    let headers = ["List", "#", "Title", "Time"];
+    let widths = table::column_widths(&headers, &rows, table::terminal_width());
+    let mut text = table::format_row(&headers, &widths) + "\n";
+    for row in &rows {
+        text += &(table::format_row(row, &widths) + "\n");
+    }
+    text
+}
+
+/// One row per item with hours as a decimal, for spreadsheets and invoices.
+pub fn to_csv(lines: &[Line]) -> String {
+    let mut csv = String::from("list,item,title,hours\n");
+    for line in lines {
+        csv += &format!(
+            "{},{},{},{:.2}\n",
+            csv_field(&line.list),
+            line.item,
+            csv_field(&line.title),
+            line.seconds as f64 / 3600.0
+        );
+    }
+    csv
+}
@@ -1,24 +1,91 @@
-use crate::db::establish_connection;
+use crate::comments::{self, Comment};
+use crate::dump::{self, DumpItem};
+use crate::events;
+use crate::journal::Snapshot;
+use crate::notifications;
+use crate::sharing::{self, Shared};
 use chrono::prelude::*;
-use rusqlite::{params, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Where a deleted item went: items deleted together share a trash id.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trash {
+    pub id: usize,
+    pub deleted_at: String,
+    pub deleted_by: String,
+}
 
 pub struct TodoItem {
+    /// Stable identity that survives renumbering and moves between lists.
+    pub uid: String,
     pub item_number: usize,
     pub title: String,
     pub datetime: String,
     pub is_completed: bool,
+    pub completed_at: Option<String>,
+    /// `YYYY-MM-DD`, or with a time `YYYY-MM-DD HH:MM:SS`.
+    pub due: Option<String>,
+    /// `A` (highest) to `Z`.
+    pub priority: Option<char>,
+    pub tags: Vec<String>,
+    pub notes: String,
+    /// Attributes from imported files that items have no field for, written
+    /// back on export so round-trips don't lose them.
+    pub extra: Map<String, Value>,
+    /// Set while the item is in the trash, where it is also `is_deleted`.
+    /// Deleted items without it are removed for good on push.
+    pub trash: Option<Trash>,
+    /// When the item was archived; archived items are left out of `show` and
+    /// `search` unless they ask for the archive.
+    pub archived_at: Option<String>,
+    /// The user name of whoever the item is assigned to.
+    pub assignee: Option<String>,
+    /// Oldest first.
+    pub comments: Vec<Comment>,
     pub is_deleted: bool,
     pub is_modified: bool,
 }
 
 impl TodoItem {
+    /// Whether the next push has to write the item: it changed, or it was
+    /// deleted for good. Items merely sitting in the trash aren't pending.
+    pub fn is_pending(&self) -> bool {
+        self.is_modified || (self.is_deleted && self.trash.is_none())
+    }
+
+    pub fn due_date(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(self.due.as_deref()?.get(..10)?, "%Y-%m-%d").ok()
+    }
+
+    /// Whether the item is still open past its due date.
+    pub fn is_overdue(&self) -> bool {
+        !self.is_completed
+            && self
+                .due_date()
+                .is_some_and(|due| due < Local::now().date_naive())
+    }
+
     pub fn new(item_number: usize, title: String) -> Self {
         TodoItem {
+            uid: Uuid::new_v4().to_string(),
             item_number,
             title,
             datetime: get_current_date_time(),
             is_completed: false,
+            completed_at: None,
+            due: None,
+            priority: None,
+            tags: Vec::new(),
+            notes: String::new(),
+            extra: Map::new(),
+            trash: None,
+            archived_at: None,
+            assignee: None,
+            comments: Vec::new(),
             is_deleted: false,
             is_modified: false,
         }
@@ -28,6 +95,9 @@ impl TodoItem {
 pub struct TodoList {
     pub title: String,
     pub todos: Vec<TodoItem>,
+    /// Set when the list belongs to another user who shared it; `title` is
+    /// then `<owner>/<list>`.
+    pub shared: Option<Shared>,
 }
 
 impl TodoList {
@@ -35,13 +105,30 @@ impl TodoList {
         TodoList {
             title,
             todos: Vec::new(),
+            shared: None,
         }
     }
 
+    fn next_item_number(&self) -> usize {
+        self.todos
+            .iter()
+            .map(|todo| todo.item_number)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
     pub fn add(&mut self, title: String) {
-        let item_number = self.todos.len() + 1;
-        let todo = TodoItem::new(item_number, title);
+        self.add_item(TodoItem::new(0, title));
+    }
+
+    /// Appends an already-built item under the next free item number.
+    pub fn add_item(&mut self, mut todo: TodoItem) -> usize {
+        let item_number = self.next_item_number();
+        todo.item_number = item_number;
+        todo.is_modified = true;
         self.todos.push(todo);
+        item_number
     }
 
     pub fn mark_complete(&mut self, item_number: usize) {
@@ -51,6 +138,7 @@ impl TodoList {
             .find(|todo| todo.item_number == item_number)
         {
             todo.is_completed = true;
+            todo.completed_at = Some(get_current_date_time());
             todo.is_modified = true;
         }
     }
@@ -62,10 +150,140 @@ impl TodoList {
             .find(|todo| todo.item_number == item_number)
         {
             todo.is_completed = false;
+            todo.completed_at = None;
+            todo.is_modified = true;
+        }
+    }
+
+    pub fn get_item(&self, item_number: usize) -> Option<&TodoItem> {
+        self.todos
+            .iter()
+            .find(|todo| todo.item_number == item_number)
+    }
+
+    /// Sets or with `None` clears an item's due date. Returns whether the
+    /// item exists.
+    pub fn set_due(&mut self, item_number: usize, due: Option<String>) -> bool {
+        match self
+            .todos
+            .iter_mut()
+            .find(|todo| todo.item_number == item_number && !todo.is_deleted)
+        {
+            Some(todo) => {
+                todo.due = due;
+                todo.is_modified = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_notes(&mut self, item_number: usize, notes: String) {
+        if let Some(todo) = self
+            .todos
+            .iter_mut()
+            .find(|todo| todo.item_number == item_number)
+        {
+            todo.notes = notes;
+            todo.is_modified = true;
+        }
+    }
+
+    pub fn append_note(&mut self, item_number: usize, text: &str) {
+        if let Some(todo) = self
+            .todos
+            .iter_mut()
+            .find(|todo| todo.item_number == item_number)
+        {
+            if !todo.notes.is_empty() && !todo.notes.ends_with('\n') {
+                todo.notes.push('\n');
+            }
+            todo.notes.push_str(text);
             todo.is_modified = true;
         }
     }
 
+    /// Archives the completed items and returns how many were archived. With
+    /// `whole_list` the list has to be finished: while any item is still open
+    /// nothing is archived and the number of open items is returned as the error.
+    pub fn archive(&mut self, whole_list: bool) -> Result<usize, usize> {
+        let open = self
+            .todos
+            .iter()
+            .filter(|todo| !todo.is_deleted && todo.archived_at.is_none() && !todo.is_completed)
+            .count();
+        if whole_list && open > 0 {
+            return Err(open);
+        }
+        let now = get_current_date_time();
+        let mut count = 0;
+        for todo in self
+            .todos
+            .iter_mut()
+            .filter(|todo| !todo.is_deleted && todo.archived_at.is_none() && todo.is_completed)
+        {
+            todo.archived_at = Some(now.clone());
+            todo.is_modified = true;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Brings an item, or with `None` every item, back out of the archive.
+    /// Returns how many came back.
+    pub fn unarchive(&mut self, item_number: Option<usize>) -> usize {
+        let mut count = 0;
+        for todo in self
+            .todos
+            .iter_mut()
+            .filter(|todo| todo.archived_at.is_some())
+            .filter(|todo| item_number.is_none_or(|number| todo.item_number == number))
+        {
+            todo.archived_at = None;
+            todo.is_modified = true;
+            count += 1;
+        }
+        count
+    }
+
+    /// Assigns an item to a user, or with `None` unassigns it. Returns whether
+    /// the item exists.
+    pub fn assign(&mut self, item_number: usize, assignee: Option<String>) -> bool {
+        match self
+            .todos
+            .iter_mut()
+            .find(|todo| todo.item_number == item_number && !todo.is_deleted)
+        {
+            Some(todo) => {
+                todo.assignee = assignee;
+                todo.is_modified = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a comment by `author` to an item. Returns whether the item exists.
+    pub fn comment(&mut self, item_number: usize, author: &str, text: String) -> bool {
+        match self
+            .todos
+            .iter_mut()
+            .find(|todo| todo.item_number == item_number && !todo.is_deleted)
+        {
+            Some(todo) => {
+                todo.comments.push(Comment {
+                    uid: Uuid::new_v4().to_string(),
+                    author: author.to_string(),
+                    created_at: get_current_date_time(),
+                    text,
+                });
+                todo.is_modified = true;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn remove_item(&mut self, item_number: usize) {
         if let Some(todo) = self
             .todos
@@ -101,129 +319,331 @@ impl User {
         self.todo_lists.get_mut(list_name)
     }
 
-    pub fn show_lists(&self) {
-        for (list_name, list) in &self.todo_lists {
-            println!("List: {}", list_name);
-            for todo in &list.todos {
-                println!(
-                    "{}. {} ({} - Completed: {})",
-                    todo.item_number, todo.title, todo.datetime, todo.is_completed
-                );
-            }
+    pub fn find_by_uid(&mut self, uid: &str) -> Option<&mut TodoItem> {
+        self.todo_lists
+            .values_mut()
+            .flat_map(|list| list.todos.iter_mut())
+            .find(|todo| todo.uid == uid)
+    }
+
+    /// Like [`find_by_uid`](Self::find_by_uid), but skips items in the trash.
+    pub fn find_live(&mut self, uid: &str) -> Option<&mut TodoItem> {
+        self.find_by_uid(uid).filter(|todo| !todo.is_deleted)
+    }
+
+    pub fn has_uid(&self, uid: &str) -> bool {
+        self.todo_lists
+            .values()
+            .flat_map(|list| list.todos.iter())
+            .any(|todo| todo.uid == uid)
+    }
+
+    fn next_trash_id(&self) -> usize {
+        self.todo_lists
+            .values()
+            .flat_map(|list| list.todos.iter())
+            .filter_map(|todo| todo.trash.as_ref().map(|trash| trash.id))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    /// Moves an item, or with `None` every item in the list, to the trash as
+    /// one entry. Returns how many items were moved.
+    pub fn trash(&mut self, list_name: &str, item_number: Option<usize>) -> usize {
+        let trash = Trash {
+            id: self.next_trash_id(),
+            deleted_at: get_current_date_time(),
+            deleted_by: self.user_name.clone(),
+        };
+        let Some(list) = self.todo_lists.get_mut(list_name) else {
+            return 0;
+        };
+        let mut count = 0;
+        for todo in list
+            .todos
+            .iter_mut()
+            .filter(|todo| !todo.is_deleted)
+            .filter(|todo| item_number.is_none_or(|number| todo.item_number == number))
+        {
+            todo.trash = Some(trash.clone());
+            todo.is_deleted = true;
+            todo.is_modified = true;
+            count += 1;
         }
+        count
     }
 
-    pub fn show_list_items(&self, list_name: &String) {
-        if let Some(list) = self.todo_lists.get(list_name) {
-            for todo in &list.todos {
-                println!(
-                    "{}. {} ({} - Completed: {})",
-                    todo.item_number, todo.title, todo.datetime, todo.is_completed
-                );
-            }
+    /// Brings back every item of a trash entry. Returns how many there were.
+    pub fn restore_from_trash(&mut self, trash_id: usize) -> usize {
+        let mut count = 0;
+        for todo in self
+            .todo_lists
+            .values_mut()
+            .flat_map(|list| list.todos.iter_mut())
+            .filter(|todo| {
+                todo.trash
+                    .as_ref()
+                    .is_some_and(|trash| trash.id == trash_id)
+            })
+        {
+            todo.trash = None;
+            todo.is_deleted = false;
+            todo.is_modified = true;
+            count += 1;
         }
+        count
     }
 
-    pub fn show_completed_items(&self, list_name: Option<&String>) {
-        match list_name {
-            Some(name) => {
-                if let Some(list) = self.todo_lists.get(name) {
-                    for todo in list.todos.iter().filter(|todo| todo.is_completed) {
-                        println!(
-                            "{}. {} ({} - Completed: {})",
-                            todo.item_number, todo.title, todo.datetime, todo.is_completed
-                        );
-                    }
-                }
-            }
-            None => {
-                for (list_name, list) in &self.todo_lists {
-                    println!("List: {}", list_name);
-                    for todo in list.todos.iter().filter(|todo| todo.is_completed) {
-                        println!(
-                            "{}. {} ({} - Completed: {})",
-                            todo.item_number, todo.title, todo.datetime, todo.is_completed
-                        );
-                    }
-                }
+    /// Deletes everything in the trash for good on the next push, leaving
+    /// lists the user can only view alone.
+    pub fn empty_trash(&mut self) -> usize {
+        let mut count = 0;
+        for list in self
+            .todo_lists
+            .values_mut()
+            .filter(|list| list.shared.as_ref().is_none_or(|s| s.role.can_edit()))
+        {
+            let trashed: Vec<usize> = list
+                .todos
+                .iter_mut()
+                .filter_map(|todo| todo.trash.take().map(|_| todo.item_number))
+                .collect();
+            for item_number in &trashed {
+                list.remove_item(*item_number);
             }
+            count += trashed.len();
         }
+        count
     }
 
-    pub fn show_incomplete_items(&self, list_name: Option<&String>) {
-        match list_name {
-            Some(name) => {
-                if let Some(list) = self.todo_lists.get(name) {
-                    for todo in list.todos.iter().filter(|todo| !todo.is_completed) {
-                        println!(
-                            "{}. {} ({} - Completed: {})",
-                            todo.item_number, todo.title, todo.datetime, todo.is_completed
-                        );
-                    }
-                }
-            }
-            None => {
-                for (list_name, list) in &self.todo_lists {
-                    println!("List: {}", list_name);
-                    for todo in list.todos.iter().filter(|todo| !todo.is_completed) {
-                        println!(
-                            "{}. {} ({} - Completed: {})",
-                            todo.item_number, todo.title, todo.datetime, todo.is_completed
-                        );
-                    }
-                }
-            }
+    pub fn get_or_add_todo_list(&mut self, list_name: &str) -> &mut TodoList {
+        self.todo_lists
+            .entry(list_name.to_string())
+            .or_insert_with(|| TodoList::new(list_name.to_string()))
+    }
+
+    /// Whether the user may change items in the list; their own lists and
+    /// lists they don't have yet always allow it.
+    pub fn can_edit(&self, list_name: &str) -> bool {
+        self.todo_lists
+            .get(list_name)
+            .and_then(|list| list.shared.as_ref())
+            .is_none_or(|shared| shared.role.can_edit())
+    }
+
+    /// The owner and real name of a list, which differ from the user and the
+    /// name they see for lists shared with them.
+    pub fn origin(&self, list_name: &str) -> Option<(&str, &str)> {
+        let list = self.todo_lists.get(list_name)?;
+        match &list.shared {
+            None => Some((&self.user_name, &list.title)),
+            Some(shared) => Some((&shared.owner, &shared.list_name)),
         }
     }
 
-    pub fn push_to_db(&self) -> Result<()> {
-        let conn = establish_connection()?;
+    /// The owner and real name of a list the user may share, unshare or
+    /// remove whole: their own, or one shared with them as owner.
+    pub fn manageable(&self, list_name: &str) -> Option<(&str, &str)> {
+        let list = self.todo_lists.get(list_name)?;
+        match &list.shared {
+            Some(shared) if !shared.role.can_manage() => None,
+            _ => self.origin(list_name),
+        }
+    }
 
+    /// Adds the user to the database if they aren't there yet, returning their id.
+    pub fn save_user(&self, conn: &Connection) -> Result<i64> {
         conn.execute(
             "INSERT OR IGNORE INTO users (user_name, email) VALUES (?1, ?2)",
             params![self.user_name, self.email],
         )?;
-
-        // Get the user ID
-        let user_id: i64 = conn.query_row(
+        conn.query_row(
             "SELECT id FROM users WHERE user_name = ?1",
             params![self.user_name],
             |row| row.get(0),
-        )?;
+        )
+    }
 
-        for (list_name, list) in &self.todo_lists {
-            for todo in &list.todos {
-                if todo.is_deleted {
-                    conn.execute(
-                        "DELETE FROM todos WHERE user_id = ?1 AND list_name = ?2 AND item_number = ?3",
-                        params![user_id, list_name, todo.item_number],
-                    )?;
-                } else if todo.is_modified {
-                    conn.execute(
-                        "UPDATE todos SET title = ?1, datetime = ?2, is_completed = ?3 WHERE user_id = ?4 AND list_name = ?5 AND item_number = ?6",
-                        params![todo.title, todo.datetime, todo.is_completed as i32, user_id, list_name, todo.item_number],
-                    )?;
-                } else {
-                    conn.execute(
-                        "INSERT OR IGNORE INTO todos (user_id, list_name, item_number, title, datetime, is_completed)
-                        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                        params![
-                            user_id,
-                            list_name,
-                            todo.item_number,
-                            todo.title,
-                            todo.datetime,
-                            todo.is_completed as i32
-                        ],
+    /// Writes the items changed since the last pull or push, moves the events
+    /// queued for the changes into the audit trail and notifies assignees and
+    /// mentioned users. Changes to shared
+    /// lists go to the owner's list, and only when the user's current role
+    /// there allows edits; viewers may only add comments, and otherwise
+    /// nothing is written. Items are matched by
+    /// uid, so edits others pushed in the meantime survive, and a new item
+    /// whose number someone else has taken gets the next free one.
+    pub fn push_to_db(&mut self, conn: &Connection) -> Result<()> {
+        let own_id = self.save_user(conn)?;
+
+        // Each list to write, and whether only its new comments are written.
+        let mut targets = Vec::new();
+        for (key, list) in &self.todo_lists {
+            if !list.todos.iter().any(TodoItem::is_pending) {
+                continue;
+            }
+            let Some(shared) = &list.shared else {
+                targets.push((key.clone(), own_id, list.title.clone(), false));
+                continue;
+            };
+            let comments_only =
+                match sharing::role(conn, &shared.owner, &shared.list_name, &self.user_name)? {
+                    Some(role) if role.can_edit() => false,
+                    Some(_) if only_comments(conn, list)? => true,
+                    Some(_) => {
+                        return Err(sharing::denied(format!("you can only view {}", list.title)))
+                    }
+                    None => {
+                        return Err(sharing::denied(format!(
+                            "{} is no longer shared with you",
+                            list.title
+                        )))
+                    }
+                };
+            let owner_id: i64 = conn.query_row(
+                "SELECT id FROM users WHERE user_name = ?1",
+                params![shared.owner],
+                |row| row.get(0),
+            )?;
+            targets.push((
+                key.clone(),
+                owner_id,
+                shared.list_name.clone(),
+                comments_only,
+            ));
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        for (key, user_id, list_name, comments_only) in targets {
+            let Some(list) = self.todo_lists.get_mut(&key) else {
+                continue;
+            };
+            let owner = match &list.shared {
+                Some(shared) => shared.owner.as_str(),
+                None => self.user_name.as_str(),
+            };
+            if comments_only {
+                for todo in list.todos.iter_mut().filter(|todo| todo.is_pending()) {
+                    for comment in &todo.comments {
+                        if comments::save(&tx, &todo.uid, comment)? {
+                            comments::notify_mentions(
+                                &tx,
+                                owner,
+                                &list_name,
+                                &todo.title,
+                                comment,
+                            )?;
+                        }
+                    }
+                    todo.is_modified = false;
+                }
+                continue;
+            }
+            let mut before = Snapshot::new();
+            let mut after = Snapshot::new();
+            for todo in list.todos.iter_mut().filter(|todo| todo.is_pending()) {
+                // A uid stored under another owner or a list we can't edit,
+                // e.g. one that came in through an import, belongs to someone
+                // else: deleting only drops our copy, and anything else is
+                // saved as a new item.
+                let queued_uid = todo.uid.clone();
+                if !writable(&tx, own_id, &self.user_name, user_id, &todo.uid)? {
+                    if todo.is_deleted && todo.trash.is_none() {
+                        events::discard(&tx, &self.user_name, Some(&todo.uid))?;
+                        continue;
+                    }
+                    todo.uid = Uuid::new_v4().to_string();
+                }
+                if let Some(state) = dump::stored(&tx, &todo.uid)? {
+                    before.insert(todo.uid.clone(), state);
+                }
+                if todo.is_deleted && todo.trash.is_none() {
+                    tx.execute("DELETE FROM todos WHERE uid = ?1", params![todo.uid])?;
+                    tx.execute(
+                        "DELETE FROM comments WHERE item_uid = ?1",
+                        params![todo.uid],
                     )?;
+                    continue;
+                }
+                todo.item_number =
+                    free_item_number(&tx, user_id, &list_name, &todo.uid, todo.item_number)?;
+                events::rekey(
+                    &tx,
+                    &self.user_name,
+                    &queued_uid,
+                    &todo.uid,
+                    todo.item_number,
+                )?;
+                tx.execute(
+                    "INSERT INTO todos (user_id, list_name, item_number, title, datetime, is_completed,
+                        completed_at, priority, tags, notes, uid, extra, trash_id, deleted_at,
+                        deleted_by, archived_at, assignee_id, due)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                        (SELECT id FROM users WHERE user_name = ?17), ?18)
+                    ON CONFLICT (uid) DO UPDATE SET
+                        user_id = excluded.user_id,
+                        list_name = excluded.list_name,
+                        item_number = excluded.item_number,
+                        title = excluded.title,
+                        datetime = excluded.datetime,
+                        is_completed = excluded.is_completed,
+                        completed_at = excluded.completed_at,
+                        priority = excluded.priority,
+                        tags = excluded.tags,
+                        notes = excluded.notes,
+                        extra = excluded.extra,
+                        trash_id = excluded.trash_id,
+                        deleted_at = excluded.deleted_at,
+                        deleted_by = excluded.deleted_by,
+                        archived_at = excluded.archived_at,
+                        assignee_id = excluded.assignee_id,
+                        due_notified = CASE WHEN due IS excluded.due THEN due_notified ELSE 0 END,
+                        due = excluded.due",
+                    params![
+                        user_id,
+                        list_name,
+                        todo.item_number,
+                        todo.title,
+                        todo.datetime,
+                        todo.is_completed as i32,
+                        todo.completed_at,
+                        todo.priority.map(String::from),
+                        todo.tags.join(" "),
+                        todo.notes,
+                        todo.uid,
+                        if todo.extra.is_empty() {
+                            String::new()
+                        } else {
+                            Value::Object(todo.extra.clone()).to_string()
+                        },
+                        todo.trash.as_ref().map(|trash| trash.id),
+                        todo.trash.as_ref().map(|trash| &trash.deleted_at),
+                        todo.trash.as_ref().map(|trash| &trash.deleted_by),
+                        todo.archived_at,
+                        todo.assignee,
+                        todo.due
+                    ],
+                )?;
+                for comment in &todo.comments {
+                    if comments::save(&tx, &todo.uid, comment)? {
+                        comments::notify_mentions(&tx, owner, &list_name, &todo.title, comment)?;
+                    }
+                }
+                if let Some(state) = dump::stored(&tx, &todo.uid)? {
+                    after.insert(todo.uid.clone(), state);
                 }
+                todo.is_modified = false;
             }
+            list.todos
+                .retain(|todo| !(todo.is_deleted && todo.trash.is_none()));
+            notifications::notify_assignees(&tx, &self.user_name, owner, &before, &after)?;
         }
-        Ok(())
+        events::flush(&tx, &self.user_name)?;
+        tx.commit()
     }
 
-    pub fn pull_from_db(user_name: &str) -> Result<Self> {
-        let conn = establish_connection()?;
+    /// Loads the user's lists and the lists shared with them.
+    pub fn pull_from_db(conn: &Connection, user_name: &str) -> Result<Self> {
         let mut stmt = conn.prepare("SELECT id, email FROM users WHERE user_name = ?1")?;
         let user_row = stmt.query_row(params![user_name], |row| {
             Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
@@ -237,26 +657,7 @@ impl User {
             todo_lists: HashMap::new(),
         };
 
-        let mut stmt = conn.prepare(
-            "SELECT list_name, item_number, title, datetime, is_completed FROM todos WHERE user_id = ?1",
-        )?;
-        let todo_iter = stmt.query_map(params![user_id], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                TodoItem {
-                    item_number: row.get(1)?,
-                    title: row.get(2)?,
-                    datetime: row.get(3)?,
-                    is_completed: row.get::<_, i32>(4)? != 0,
-                    is_deleted: false,
-                    is_modified: false,
-                },
-            ))
-        })?;
-
-        for todo in todo_iter {
-            let (list_name, todo) = todo?;
-
+        for (list_name, todo) in load_items(conn, user_id, None)? {
             user.todo_lists
                 .entry(list_name.clone())
                 .or_insert_with(|| TodoList::new(list_name.clone()))
@@ -264,11 +665,179 @@ impl User {
                 .push(todo);
         }
 
+        for shared in sharing::shared_with(conn, user_name)? {
+            let owner_id: i64 = conn.query_row(
+                "SELECT id FROM users WHERE user_name = ?1",
+                params![shared.owner],
+                |row| row.get(0),
+            )?;
+            let title = sharing::shared_name(&shared.owner, &shared.list_name);
+            let mut list = TodoList::new(title.clone());
+            list.todos = load_items(conn, owner_id, Some(&shared.list_name))?
+                .into_iter()
+                .map(|(_, todo)| todo)
+                .collect();
+            list.shared = Some(shared);
+            user.todo_lists.insert(title, list);
+        }
+
         Ok(user)
     }
 }
 
-fn get_current_date_time() -> String {
+/// Whether the only unpushed changes to a list are comments, on items that
+/// are otherwise as stored. Anything else on a viewer's list, such as edits
+/// made before they were demoted, can't be pushed.
+fn only_comments(conn: &Connection, list: &TodoList) -> Result<bool> {
+    for todo in list.todos.iter().filter(|todo| todo.is_pending()) {
+        let Some((_, stored)) = dump::stored(conn, &todo.uid)? else {
+            return Ok(false);
+        };
+        let local = DumpItem {
+            comments: Vec::new(),
+            ..DumpItem::from_item(todo)
+        };
+        if todo.is_deleted
+            || local
+                != (DumpItem {
+                    comments: Vec::new(),
+                    ..stored
+                })
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether the stored item with `uid`, if any, belongs to `owner_id` and is
+/// in one of the user's own lists or a list shared with them as an editor.
+fn writable(
+    conn: &Connection,
+    own_id: i64,
+    user_name: &str,
+    owner_id: i64,
+    uid: &str,
+) -> Result<bool> {
+    let stored: Option<(i64, String, String)> = conn
+        .query_row(
+            "SELECT t.user_id, u.user_name, t.list_name FROM todos t
+            JOIN users u ON u.id = t.user_id WHERE t.uid = ?1",
+            params![uid],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    match stored {
+        None => Ok(true),
+        Some((user_id, _, _)) if user_id != owner_id => Ok(false),
+        Some((user_id, _, _)) if user_id == own_id => Ok(true),
+        Some((_, owner, list_name)) => {
+            Ok(sharing::role(conn, &owner, &list_name, user_name)?
+                .is_some_and(|role| role.can_edit()))
+        }
+    }
+}
+
+/// The number to store an item under in a list: the one it has, unless
+/// another item already took it there, in which case the one it was stored
+/// under before or else the next free one.
+fn free_item_number(
+    conn: &Connection,
+    user_id: i64,
+    list_name: &str,
+    uid: &str,
+    item_number: usize,
+) -> Result<usize> {
+    let holder: Option<String> = conn
+        .query_row(
+            "SELECT uid FROM todos WHERE user_id = ?1 AND list_name = ?2 AND item_number = ?3",
+            params![user_id, list_name, item_number],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if holder.is_none_or(|holder| holder == uid) {
+        return Ok(item_number);
+    }
+    let stored: Option<usize> = conn
+        .query_row(
+            "SELECT item_number FROM todos WHERE user_id = ?1 AND list_name = ?2 AND uid = ?3",
+            params![user_id, list_name, uid],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match stored {
+        Some(number) => Ok(number),
+        None => conn.query_row(
+            "SELECT COALESCE(MAX(item_number), 0) + 1 FROM todos
+            WHERE user_id = ?1 AND list_name = ?2",
+            params![user_id, list_name],
+            |row| row.get(0),
+        ),
+    }
+}
+
+/// Reads a user's items, or only those in one list, with their list names.
+fn load_items(
+    conn: &Connection,
+    user_id: i64,
+    list_name: Option<&str>,
+) -> Result<Vec<(String, TodoItem)>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.list_name, t.item_number, t.title, t.datetime, t.is_completed, t.completed_at,
+            t.priority, t.tags, t.notes, t.uid, t.extra, t.trash_id, t.deleted_at, t.deleted_by,
+            t.archived_at, a.user_name, t.due
+        FROM todos t LEFT JOIN users a ON a.id = t.assignee_id
+        WHERE t.user_id = ?1 AND (?2 IS NULL OR t.list_name = ?2)
+        ORDER BY t.list_name, t.item_number",
+    )?;
+    let todo_iter = stmt.query_map(params![user_id, list_name], |row| {
+        let trash = match row.get::<_, Option<usize>>(11)? {
+            Some(id) => Some(Trash {
+                id,
+                deleted_at: row.get(12)?,
+                deleted_by: row.get(13)?,
+            }),
+            None => None,
+        };
+        Ok((
+            row.get::<_, String>(0)?,
+            TodoItem {
+                uid: row
+                    .get::<_, Option<String>>(9)?
+                    .unwrap_or_else(|| Uuid::new_v4().to_string()),
+                item_number: row.get(1)?,
+                title: row.get(2)?,
+                datetime: row.get(3)?,
+                is_completed: row.get::<_, i32>(4)? != 0,
+                completed_at: row.get(5)?,
+                due: row.get(16)?,
+                priority: row
+                    .get::<_, Option<String>>(6)?
+                    .and_then(|p| p.chars().next()),
+                tags: row
+                    .get::<_, String>(7)?
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect(),
+                notes: row.get(8)?,
+                extra: serde_json::from_str(&row.get::<_, String>(10)?).unwrap_or_default(),
+                is_deleted: trash.is_some(),
+                trash,
+                archived_at: row.get(14)?,
+                assignee: row.get(15)?,
+                comments: Vec::new(),
+                is_modified: false,
+            },
+        ))
+    })?;
+    let mut items: Vec<(String, TodoItem)> = todo_iter.collect::<Result<_>>()?;
+    for (_, todo) in &mut items {
+        todo.comments = comments::load(conn, &todo.uid)?;
+    }
+    Ok(items)
+}
+
+pub fn get_current_date_time() -> String {
     let local_now = Local::now();
     local_now.format("%Y-%m-%d %H:%M:%S").to_string()
 }
@@ -0,0 +1,244 @@
+//! The [todo.txt](https://github.com/todotxt/todo.txt) format.
+//!
+//! `x` marks completion, `(A)` the priority, the first `+project` the list,
+//! each `@context` a tag and `due:YYYY-MM-DD` the due date. Completed tasks
+//! keep their priority as `pri:A`. Other `+project` and `key:value` words stay
+//! in the title. Spaces in list names are written as `_` and read back as
+//! spaces.
+//!
+//! The list is written before the title, so `+project` words in a title come
+//! back as title words. Items that would not read back the same, such as a
+//! title with an `@context` word or a list name with `_` in it, are refused
+//! on export. Fields todo.txt has no place for (notes, comments, assignees,
+//! due times and attributes kept from other formats) are left out, and
+//! [`left_out`] says which.
+
+use crate::exchange::ImportReport;
+use crate::todo::{TodoItem, User};
+use chrono::NaiveDate;
+
+fn is_date_like(word: &str) -> bool {
+    word.len() == 10
+        && word.chars().enumerate().all(|(i, c)| {
+            if i == 4 || i == 7 {
+                c == '-'
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+}
+
+/// Consumes a leading `YYYY-MM-DD` word, failing if it looks like a date but
+/// is not a valid one.
+fn take_date(words: &mut Vec<&str>) -> Result<Option<String>, String> {
+    match words.first() {
+        Some(word) if is_date_like(word) => {
+            NaiveDate::parse_from_str(word, "%Y-%m-%d")
+                .map_err(|_| format!("invalid date '{}'", word))?;
+            Ok(Some(format!("{} 00:00:00", words.remove(0))))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn take_priority(words: &mut Vec<&str>) -> Option<char> {
+    let word = words.first()?;
+    let bytes = word.as_bytes();
+    if bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')' {
+        words.remove(0);
+        Some(bytes[1] as char)
+    } else {
+        None
+    }
+}
+
+/// Parses one line into the list it names (if any) and an item.
+pub fn parse_line(line: &str) -> Result<(Option<String>, TodoItem), String> {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    let is_completed = words.first() == Some(&"x");
+    if is_completed {
+        words.remove(0);
+    }
+    let mut priority = take_priority(&mut words);
+    let (completed_at, created) = if is_completed {
+        let first = take_date(&mut words)?;
+        let second = take_date(&mut words)?;
+        (first, second)
+    } else {
+        (None, take_date(&mut words)?)
+    };
+
+    let mut list_name = None;
+    let mut tags = Vec::new();
+    let mut due = None;
+    let mut title = Vec::new();
+    for word in words {
+        if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            if list_name.is_none() {
+                list_name = Some(project.replace('_', " "));
+                continue;
+            }
+        } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            tags.push(context.to_string());
+            continue;
+        } else if let Some(p) = word.strip_prefix("pri:") {
+            if let (Some(c), 1) = (p.chars().next(), p.len()) {
+                if c.is_ascii_uppercase() && priority.is_none() {
+                    priority = Some(c);
+                    continue;
+                }
+            }
+        } else if let Some(date) = word.strip_prefix("due:").filter(|d| is_date_like(d)) {
+            if due.is_none() {
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid date '{}'", date))?;
+                due = Some(date.to_string());
+                continue;
+            }
+        }
+        title.push(word);
+    }
+    if title.is_empty() {
+        return Err("missing description".to_string());
+    }
+
+    let mut todo = TodoItem::new(0, title.join(" "));
+    if let Some(created) = created {
+        todo.datetime = created;
+    }
+    todo.is_completed = is_completed;
+    todo.completed_at = completed_at;
+    todo.priority = priority;
+    todo.tags = tags;
+    todo.due = due;
+    Ok((list_name, todo))
+}
+
+fn is_priority_word(word: &str) -> bool {
+    matches!(word.strip_prefix("pri:"), Some(p) if p.len() == 1 && p.as_bytes()[0].is_ascii_uppercase())
+}
+
+fn is_due_word(word: &str) -> bool {
+    word.strip_prefix("due:").is_some_and(is_date_like)
+}
+
+/// Why an item can't be written so that [`parse_line`] reads it back the same.
+fn unrepresentable(list_name: &str, todo: &TodoItem) -> Option<String> {
+    if list_name.contains('_')
+        || list_name.split_whitespace().collect::<Vec<_>>().join(" ") != list_name
+    {
+        return Some(format!(
+            "list name '{}' can't be written as a +project",
+            list_name
+        ));
+    }
+    if let Some(word) = todo.title.split_whitespace().find(|word| {
+        (word.len() > 1 && word.starts_with('@')) || is_priority_word(word) || is_due_word(word)
+    }) {
+        return Some(format!(
+            "item {} of {} has '{}' in its title",
+            todo.item_number, list_name, word
+        ));
+    }
+    if let Some(tag) = todo
+        .tags
+        .iter()
+        .find(|tag| tag.is_empty() || tag.contains(char::is_whitespace))
+    {
+        return Some(format!(
+            "item {} of {} has the tag '{}'",
+            todo.item_number, list_name, tag
+        ));
+    }
+    None
+}
+
+pub fn format_item(list_name: &str, todo: &TodoItem) -> String {
+    let mut words = Vec::new();
+    let created = todo.datetime.get(..10).unwrap_or(&todo.datetime);
+    if todo.is_completed {
+        words.push("x".to_string());
+        let completed = todo.completed_at.as_deref().unwrap_or(&todo.datetime);
+        words.push(completed.get(..10).unwrap_or(completed).to_string());
+    } else if let Some(priority) = todo.priority {
+        words.push(format!("({})", priority));
+    }
+    words.push(created.to_string());
+    words.push(format!("+{}", list_name.replace(' ', "_")));
+    words.push(todo.title.split_whitespace().collect::<Vec<_>>().join(" "));
+    for tag in &todo.tags {
+        words.push(format!("@{}", tag));
+    }
+    if let (true, Some(priority)) = (todo.is_completed, todo.priority) {
+        words.push(format!("pri:{}", priority));
+    }
+    if let Some(due) = &todo.due {
+        words.push(format!("due:{}", due.get(..10).unwrap_or(due)));
+    }
+    words.join(" ")
+}
+
+/// What [`export`] leaves out of these lists, such as `notes of 2 items`.
+pub fn left_out(user: &User, lists: &[&str]) -> Vec<String> {
+    let todos: Vec<&TodoItem> = lists
+        .iter()
+        .flat_map(|list_name| user.todo_lists[*list_name].todos.iter())
+        .filter(|t| !t.is_deleted)
+        .collect();
+    type Has = fn(&TodoItem) -> bool;
+    let fields: [(&str, Has); 5] = [
+        ("notes", |t| !t.notes.trim().is_empty()),
+        ("comments", |t| !t.comments.is_empty()),
+        ("assignees", |t| t.assignee.is_some()),
+        ("due times", |t| {
+            t.due.as_ref().is_some_and(|due| due.len() > 10)
+        }),
+        ("other attributes", |t| !t.extra.is_empty()),
+    ];
+    fields
+        .into_iter()
+        .filter_map(
+            |(field, has)| match todos.iter().filter(|t| has(t)).count() {
+                0 => None,
+                1 => Some(format!("{} of 1 item", field)),
+                n => Some(format!("{} of {} items", field, n)),
+            },
+        )
+        .collect()
+}
+
+pub fn import(user: &mut User, text: &str, default_list: &str) -> ImportReport {
+    let mut report = ImportReport::default();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line) {
+            Ok((list_name, todo)) => {
+                let list_name = list_name.unwrap_or_else(|| default_list.to_string());
+                user.get_or_add_todo_list(&list_name).add_item(todo);
+                report.imported += 1;
+            }
+            Err(e) => report.errors.push((index + 1, e)),
+        }
+    }
+    report
+}
+
+pub fn export(user: &User, lists: &[&str]) -> Result<String, String> {
+    let mut out = String::new();
+    for list_name in lists {
+        for todo in user.todo_lists[*list_name]
+            .todos
+            .iter()
+            .filter(|t| !t.is_deleted)
+        {
+            if let Some(reason) = unrepresentable(list_name, todo) {
+                return Err(format!("Can't export to todo.txt: {}.", reason));
+            }
+            out.push_str(&format_item(list_name, todo));
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,78 @@
+//! Listing and purging the trash. Moving items in and out of it is done by
+//! [`User::trash`] and [`User::restore_from_trash`].
+
+use crate::listing::Row;
+use crate::todo::{TodoItem, Trash, User};
+use chrono::{Duration, Local};
+use rusqlite::{params, Connection, Result};
+
+/// Items deleted together: one item, or a whole list.
+#[derive(Clone)]
+pub struct Entry<'a> {
+    pub trash: &'a Trash,
+    pub list_name: &'a str,
+    pub items: Vec<&'a TodoItem>,
+}
+
+impl<'a> Entry<'a> {
+    /// The first item, which stands for the entry when sorting and grouping.
+    pub fn row(&self) -> Row<'a> {
+        Row {
+            list_name: self.list_name,
+            todo: self.items[0],
+        }
+    }
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn entries(user: &User) -> Vec<Entry<'_>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (list_name, list) in &user.todo_lists {
+        for todo in &list.todos {
+            let Some(trash) = &todo.trash else {
+                continue;
+            };
+            match entries.iter_mut().find(|entry| entry.trash.id == trash.id) {
+                Some(entry) => entry.items.push(todo),
+                None => entries.push(Entry {
+                    trash,
+                    list_name,
+                    items: vec![todo],
+                }),
+            }
+        }
+    }
+    for entry in &mut entries {
+        entry.items.sort_by_key(|todo| todo.item_number);
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.trash.id));
+    entries
+}
+
+/// Deletes items that have been in the trash for more than `days` days,
+/// recording a `purge` event for each. Returns how many were deleted.
+pub fn purge_expired(conn: &Connection, days: u32) -> Result<usize> {
+    let now = Local::now();
+    let cutoff = (now - Duration::days(days.into()))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO todo_events (uid, owner, list_name, item_number, actor, at, kind, old_value)
+        SELECT t.uid, u.user_name, t.list_name, t.item_number, 'auto-purge', ?2, 'purge', t.title
+        FROM todos t JOIN users u ON u.id = t.user_id
+        WHERE t.trash_id IS NOT NULL AND t.deleted_at < ?1",
+        params![cutoff, now.format("%Y-%m-%d %H:%M:%S").to_string()],
+    )?;
+    tx.execute(
+        "DELETE FROM comments WHERE item_uid IN
+            (SELECT uid FROM todos WHERE trash_id IS NOT NULL AND deleted_at < ?1)",
+        params![cutoff],
+    )?;
+    let purged = tx.execute(
+        "DELETE FROM todos WHERE trash_id IS NOT NULL AND deleted_at < ?1",
+        params![cutoff],
+    )?;
+    tx.commit()?;
+    Ok(purged)
+}